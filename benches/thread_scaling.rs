@@ -0,0 +1,43 @@
+//! Measures how [`Board::solve_with_threads`] scales with the number of threads.
+//!
+//!     cargo bench --features thread
+
+use molek_syntez_solitaire::cards::*;
+use molek_syntez_solitaire::heuristics::*;
+use molek_syntez_solitaire::solver::*;
+use std::time::{Duration, Instant};
+
+fn main() {
+    // The same deals every run
+    let boards: Vec<Board> = (1..=20).map(Board::deal).collect();
+    // The most threads to measure defaults to the number of CPUs, e.g. `cargo bench --features thread -- 8`
+    let cpus = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or_else(num_cpus::get);
+    let thread_counts: Vec<usize> = std::iter::successors(Some(1), |&n| Some(n * 2))
+        .take_while(|&n| n < cpus)
+        .chain(Some(cpus))
+        .collect();
+    // A coarse heuristic leaves many ties, which are broken by fewest moves, so each search expands enough
+    // boards to be worth parallelizing (`Board::solve` usually finishes in a few milliseconds).
+    let heuristic = |board: &Board| board.lower_bound() / 3;
+    let mut baseline = None;
+    println!("threads  time        speedup  solved");
+    for threads in thread_counts {
+        let mut elapsed = Duration::ZERO;
+        let mut solved = 0;
+        for board in &boards {
+            let start = Instant::now();
+            let solution = board.clone().solve_with_threads(heuristic, MoveCount, &SolveOptions::default(), threads).solution();
+            elapsed += start.elapsed();
+            solved += solution.is_some() as usize;
+        }
+        let baseline = *baseline.get_or_insert(elapsed);
+        println!(
+            "{:<8} {:<11} {:<8.2} {}/{}",
+            threads,
+            format!("{:.3?}", elapsed),
+            baseline.as_secs_f64() / elapsed.as_secs_f64(),
+            solved,
+            boards.len(),
+        );
+    }
+}
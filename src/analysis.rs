@@ -0,0 +1,249 @@
+use crate::cards::*;
+use crate::heuristics::*;
+use crate::moves::*;
+use crate::solver::*;
+
+/// Whether a board can still be solved
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Solvability {
+    /// The shortest solution takes `remaining` moves
+    Solvable { remaining: usize },
+    Unsolvable,
+    /// A limit was reached before the board was solved or proven unsolvable
+    Unknown(Limit),
+    /// The board is not a legal position
+    Invalid(InvalidBoard),
+}
+
+impl Solvability {
+    /// The result of a search for a shortest solution, e.g. with [`Board::solve_optimal_with`]
+    pub fn new<C>(outcome: SolveOutcome<C>) -> Self {
+        match outcome {
+            SolveOutcome::Solved { moves, .. } => Solvability::Solvable { remaining: moves.len() },
+            SolveOutcome::Unsolvable { .. } => Solvability::Unsolvable,
+            SolveOutcome::GaveUp { limit, .. } => Solvability::Unknown(limit),
+            SolveOutcome::Invalid(error) => Solvability::Invalid(error),
+        }
+    }
+}
+
+impl std::fmt::Display for Solvability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Solvability::Solvable { remaining } => write!(f, "solvable in {} moves", remaining),
+            Solvability::Unsolvable => f.write_str("unsolvable"),
+            Solvability::Unknown(limit) => write!(f, "unknown ({:?} limit reached)", limit),
+            Solvability::Invalid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// The result of making one legal move from a board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveAnalysis {
+    pub move_: Move,
+    /// Whether the board after `move_` can be solved
+    pub solvability: Solvability,
+}
+
+/// Analyzes every legal move from `board`, best first: solvable moves in order of the length of the remaining
+/// solution, then unsolvable moves, then those whose solvability is unknown.
+///
+/// The board after each move is searched with [`Board::solve_optimal_with`], and the limits in `options`
+/// apply to each of those searches separately. Fails if `board` is not a legal position.
+pub fn analyze(board: &Board, options: &SolveOptions) -> Result<Vec<MoveAnalysis>, InvalidBoard> {
+    board.validate()?;
+    let mut analyses: Vec<MoveAnalysis> = board.possible_moves().into_iter().filter_map(|move_| {
+        let board = move_.apply(board)?;
+        // Moves keep boards legal, so this is never `Invalid`
+        let solvability = Solvability::new(board.solve_optimal_with(LowerBound, MoveCount, options));
+        Some(MoveAnalysis { move_, solvability })
+    }).collect();
+    analyses.sort_by(|a, b| (&a.solvability, a.move_).cmp(&(&b.solvability, b.move_)));
+    Ok(analyses)
+}
+
+/// The next move to make towards solving a board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hint {
+    /// Make `move_`, after which the solution has `remaining` more moves, `cheats` of which are cheats
+    Move { move_: Move, remaining: usize, cheats: usize },
+    /// The board is already solved
+    Solved,
+    /// The board can't be solved
+    Unsolvable,
+    /// A limit was reached before finding a solution
+    Unknown(Limit),
+    /// The board is not a legal position
+    Invalid(InvalidBoard),
+}
+
+/// The first move of a solution of `board` with the fewest moves or, if `min_cheats`, with the fewest cheats.
+pub fn hint(board: &Board, min_cheats: bool, options: &SolveOptions) -> Hint {
+    fn moves<C>(outcome: SolveOutcome<C>) -> Result<Vec<Move>, Hint> {
+        match outcome {
+            SolveOutcome::Solved { moves, .. } => Ok(moves),
+            SolveOutcome::Unsolvable { .. } => Err(Hint::Unsolvable),
+            SolveOutcome::GaveUp { limit, .. } => Err(Hint::Unknown(limit)),
+            SolveOutcome::Invalid(error) => Err(Hint::Invalid(error)),
+        }
+    }
+    let moves = if min_cheats {
+        moves(board.clone().solve_optimal_with(CheatLowerBound, CheatsThenMoves, options))
+    } else {
+        moves(board.clone().solve_optimal_with(LowerBound, MoveCount, options))
+    };
+    let moves = match moves {
+        Ok(moves) => moves,
+        Err(hint) => return hint,
+    };
+    match moves.split_first() {
+        Some((&move_, rest)) => Hint::Move {
+            move_,
+            remaining: rest.len(),
+            cheats: rest.iter().filter(|move_| move_.is_cheat()).count(),
+        },
+        None => Hint::Solved,
+    }
+}
+
+/// What makes a board hard to solve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difficulty {
+    /// The number of boards [`Board::solve_optimal`] expands to find the shortest solution
+    pub expanded: usize,
+    /// The length of the shortest solution
+    pub moves: usize,
+    /// The fewest cheats in any solution
+    pub cheats: usize,
+    /// The number of legal first moves after which the board can still be solved
+    pub winning_moves: usize,
+    /// The number of legal first moves whose search reached a limit, so that it is unknown whether they win
+    pub unknown_moves: usize,
+    pub legal_moves: usize,
+}
+
+impl Difficulty {
+    /// Rates `board` by searching for its shortest solution, its solution with the fewest cheats,
+    /// and a solution after each legal first move (with [`Board::solve_with`], which is much faster).
+    ///
+    /// The limits in `options` apply to each search separately. Fails if `board` has no solution, is not a
+    /// legal position, or a limit was reached in one of the first two searches. First moves whose search
+    /// reaches a limit are counted in `unknown_moves`, and left out of the score.
+    pub fn new(board: &Board, options: &SolveOptions) -> Result<Self, SolveError> {
+        let (moves, _, stats) = board.clone().solve_optimal_with(LowerBound, MoveCount, options).into_result()?;
+        let (_, cost, _) = board.clone().solve_optimal_with(CheatLowerBound, CheatsThenMoves, options).into_result()?;
+        let first_moves = board.possible_moves();
+        let mut winning_moves = 0;
+        let mut unknown_moves = 0;
+        for move_ in &first_moves {
+            match move_.apply(board).unwrap().solve_with(Score, MoveCount, options).into_result() {
+                Ok(_) => winning_moves += 1,
+                Err(SolveError::Unsolvable) => {},
+                Err(SolveError::GaveUp(_)) => unknown_moves += 1,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(Self {
+            expanded: stats.expanded,
+            moves: moves.len(),
+            cheats: cost.cheats,
+            winning_moves,
+            unknown_moves,
+            legal_moves: first_moves.len(),
+        })
+    }
+    /// A single number, higher for harder boards.
+    ///
+    /// Each doubling of the search effort adds 1, each cheat 2 and each move 1/4,
+    /// and the fraction of first moves that lose the game, out of those known to win or lose, is scaled to
+    /// add up to 5.
+    pub fn score(&self) -> f64 {
+        let losing = match self.legal_moves - self.unknown_moves {
+            0 => 0.0,
+            known => 1.0 - self.winning_moves as f64 / known as f64,
+        };
+        (1.0 + self.expanded as f64).log2()
+            + 2.0 * self.cheats as f64
+            + 0.25 * self.moves as f64
+            + 5.0 * losing
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Difficulty:      {:.1}", self.score())?;
+        writeln!(f, "Boards expanded: {}", self.expanded)?;
+        writeln!(f, "Shortest:        {} moves", self.moves)?;
+        writeln!(f, "Fewest cheats:   {}", self.cheats)?;
+        write!(f, "Winning moves:   {} of {}", self.winning_moves, self.legal_moves)?;
+        if self.unknown_moves > 0 {
+            write!(f, " ({} unknown)", self.unknown_moves)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::tests::{play, solvable_deals};
+
+    #[test]
+    fn analysis_matches_optimal_solution() {
+        for board in solvable_deals() {
+            let (_, optimal) = board.clone().solve_optimal().unwrap();
+            // Start near the end of the solution, to keep the searches short
+            let board = play(board, &optimal[..optimal.len() - 8]);
+            let analyses = analyze(&board, &SolveOptions::default()).unwrap();
+            assert_eq!(analyses.len(), board.possible_moves().len());
+            assert_eq!(analyses[0].solvability, Solvability::Solvable { remaining: 7 });
+            match hint(&board, false, &SolveOptions::default()) {
+                Hint::Move { move_, remaining: 7, .. } => assert!(analyses.iter().any(|analysis| {
+                    analysis.move_ == move_ && analysis.solvability == Solvability::Solvable { remaining: 7 }
+                })),
+                hint => panic!("{:?}", hint),
+            }
+            for analysis in &analyses {
+                if let Solvability::Solvable { remaining } = analysis.solvability {
+                    assert!(remaining >= 7);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_boards_are_reported() {
+        let mut board = Board::deal(1);
+        board.columns.pop();
+        let error = board.validate().unwrap_err();
+        let options = SolveOptions::default();
+        assert_eq!(analyze(&board, &options), Err(error.clone()));
+        assert_eq!(hint(&board, false, &options), Hint::Invalid(error.clone()));
+        assert_eq!(Difficulty::new(&board, &options), Err(SolveError::Invalid(error.clone())));
+        assert!(matches!(crate::solutions::MinimalSolutions::new(&board, &options), Err(SolveError::Invalid(_))));
+        let outcome = board.solve_optimal_with(LowerBound, MoveCount, &options);
+        assert_eq!(Solvability::new(outcome), Solvability::Invalid(error));
+    }
+
+    #[test]
+    fn difficulty_of_deal() {
+        let board = Board::deal(4);
+        let difficulty = Difficulty::new(&board, &SolveOptions::default()).unwrap();
+        let (_, moves) = board.clone().solve_optimal().unwrap();
+        let (_, _, cost) = board.clone().solve_min_cheats().unwrap();
+        assert_eq!(difficulty.moves, moves.len());
+        assert_eq!(difficulty.cheats, cost.cheats);
+        assert_eq!(difficulty.legal_moves, board.possible_moves().len());
+        assert!(difficulty.winning_moves >= 1);
+        assert_eq!(difficulty.unknown_moves, 0);
+        // Partway through the solution is easier
+        let board = play(board, &moves[..20]);
+        let easier = Difficulty::new(&board, &SolveOptions::default()).unwrap();
+        assert_eq!(easier.moves, moves.len() - 20);
+        assert!(easier.score() < difficulty.score());
+        // First moves that weren't decided don't count as losing
+        let unknown = Difficulty { unknown_moves: 2, legal_moves: difficulty.legal_moves + 2, ..difficulty.clone() };
+        assert_eq!(unknown.score(), difficulty.score());
+    }
+}
@@ -1,16 +1,25 @@
-use std::collections::{VecDeque, HashSet, BinaryHeap, HashMap, BTreeSet};
-use std::rc::Rc;
+use std::collections::{VecDeque, HashSet, BinaryHeap, HashMap, BTreeSet, BTreeMap};
 use std::num::NonZeroUsize;
 use crate::moves::*;
+use crate::heuristics::*;
+use crate::packed::*;
+use crate::solver::*;
 #[cfg(feature = "thread")]
-use std::sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, Ordering}};
+use std::sync::{Condvar, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
 
+/// With the `serde` feature, cards are serialized as their glyphs from [`Card::to_str`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Card {
+    #[cfg_attr(feature = "serde", serde(rename = "6"))]
     Six,
+    #[cfg_attr(feature = "serde", serde(rename = "7"))]
     Seven,
+    #[cfg_attr(feature = "serde", serde(rename = "8"))]
     Eight,
+    #[cfg_attr(feature = "serde", serde(rename = "9"))]
     Nine,
+    #[cfg_attr(feature = "serde", serde(rename = "X"))]
     Ten,
     V,
     D,
@@ -19,6 +28,11 @@ pub enum Card {
 }
 
 impl Card {
+    /// All cards, from lowest to highest
+    pub const ALL: [Card; 9] = {
+        use Card::*;
+        [Six, Seven, Eight, Nine, Ten, V, D, K, T]
+    };
     pub fn goes_on(&self) -> Option<Self> {
         use Card::*;
         Some(match self {
@@ -47,6 +61,10 @@ impl Card {
             T => "T",
         }
     }
+    /// The inverse of [`Card::to_str`]
+    pub fn from_char(glyph: char) -> Option<Self> {
+        Card::ALL.into_iter().find(|card| card.to_str().starts_with(glyph))
+    }
     pub fn to_str_cheat(&self) -> &'static str {
         use Card::*;
         match self {
@@ -64,11 +82,14 @@ impl Card {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Board {
     pub columns: Vec<Column>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum Column {
     Solved,
     Unsolved {
@@ -77,214 +98,779 @@ pub enum Column {
     },
 }
 
+/// A borrowed [`Column`], so that [`Board`] and [`PackedBoard`] can share code.
+///
+/// Ordered the same way as [`Column`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ColumnView<'a> {
+    Solved,
+    Unsolved {
+        cards: &'a [Card],
+        cheat: Option<Card>,
+    },
+}
+
+impl Column {
+    /// The cards of a column that becomes [`Column::Solved`]
+    pub const SOLVED_CARDS: [Card; 9] = {
+        use Card::*;
+        [T, K, D, V, Ten, Nine, Eight, Seven, Six]
+    };
+    pub(crate) fn view(&self) -> ColumnView<'_> {
+        match self {
+            Column::Solved => ColumnView::Solved,
+            Column::Unsolved { cards, cheat } => ColumnView::Unsolved { cards, cheat: *cheat },
+        }
+    }
+}
+
+impl ColumnView<'_> {
+    /// See [`Board::score`]
+    pub(crate) fn score(self) -> i64 {
+        match self {
+            ColumnView::Solved => -1024,
+            ColumnView::Unsolved { cards, cheat: None } => {
+                let mut depth = 1;
+                for w in cards.windows(2).rev() {
+                    if let [c1, c2] = w {
+                        if c2.goes_on() == Some(*c1) { depth += 1; }
+                    }
+                }
+                -depth * depth
+            },
+            ColumnView::Unsolved { cards, cheat: Some(_) } => {
+                // Intentionally different from no cheat, since having a cheat on top of a lot of cards is bad(?)
+                (cards.len() * cards.len()) as i64 + 256
+            },
+        }
+    }
+    /// See [`Board::lower_bound`]
+    pub(crate) fn lower_bound(self) -> usize {
+        let mut bound = 0;
+        if let ColumnView::Unsolved { cards, cheat } = self {
+            if cards.first().is_some_and(|&card| card != Card::T) { bound += 1; }
+            bound += cards.windows(2).filter(|w| w[1].goes_on() != Some(w[0])).count();
+            if cheat.is_some() { bound += 1; }
+        }
+        bound
+    }
+}
+
+/// All moves for which [`Move::apply`] would succeed on a board with the given `columns`.
+pub(crate) fn possible_moves(columns: &[ColumnView]) -> Vec<Move> {
+    let mut moves = Vec::with_capacity(64);
+    for (from, from_col) in columns.iter().enumerate() {
+        match from_col {
+            ColumnView::Solved => {},
+            ColumnView::Unsolved { cheat: Some(cheat), .. } => {
+                for (to, to_col) in columns.iter().enumerate() {
+                    match to_col {
+                        ColumnView::Solved => {},
+                        ColumnView::Unsolved { cheat: Some(_), .. } => {},
+                        ColumnView::Unsolved { cards: to_cards, cheat: None } => {
+                            if to_cards.is_empty() || to_cards.last().copied() == cheat.goes_on() {
+                                moves.push(Move::UnCheat { from, to });
+                            }
+                        },
+                    };
+                }
+            },
+            ColumnView::Unsolved { cards: from_cards, cheat: None } => {
+                let Some(&top) = from_cards.last() else { continue; };
+                // Length of the in-sequence run on top of the column
+                let run = 1 + from_cards.windows(2).rev()
+                    .take_while(|w| w[1].goes_on() == Some(w[0]))
+                    .count();
+                for (to, to_col) in columns.iter().enumerate() {
+                    if from == to { continue; }
+                    match to_col {
+                        ColumnView::Solved => {},
+                        ColumnView::Unsolved { cheat: Some(_), .. } => {},
+                        ColumnView::Unsolved { cards: to_cards, cheat: None } => {
+                            match to_cards.last() {
+                                // Any run can be placed on an empty column
+                                None => moves.extend((1..=run).map(
+                                    |count| Move::Normal { from, to, count: NonZeroUsize::new(count).unwrap() }
+                                )),
+                                Some(&to_top) => {
+                                    // At most one card in the run goes on `to_top`
+                                    let count = (1..=run).find(|&count| from_cards[from_cards.len() - count].goes_on() == Some(to_top));
+                                    if let Some(count) = count {
+                                        moves.push(Move::Normal { from, to, count: NonZeroUsize::new(count).unwrap() });
+                                    }
+                                    if top.goes_on() != Some(to_top) {
+                                        moves.push(Move::Cheat { from, to });
+                                    }
+                                },
+                            }
+                        },
+                    };
+                }
+            },
+        };
+    }
+    moves
+}
+
+/// A board in the queue of [`Board::solve_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GreedyItem<E, C> {
+    estimate: E,
+    cost: C,
+    board: PackedBoard,
+    /// The index of the parent board in the [`MoveTree`], and the move from it to this board
+    last: Option<(usize, Move)>,
+    /// The number of moves to this board
+    depth: usize,
+}
+
+impl<E, C> GreedyItem<E, C> {
+    fn new<H: Heuristic<Estimate = E>>(heuristic: &H, board: PackedBoard, cost: C, last: Option<(usize, Move)>) -> Self {
+        Self { estimate: heuristic.estimate_packed(&board), cost, board, last, depth: 0 }
+    }
+    /// Sets the depth of a child of a board at `depth`
+    fn deeper(self, depth: usize) -> Self {
+        Self { depth: depth + 1, ..self }
+    }
+}
+
+impl<E: Ord, C: Ord> std::cmp::PartialOrd for GreedyItem<E, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E: Ord, C: Ord> std::cmp::Ord for GreedyItem<E, C> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.estimate.cmp(&other.estimate).reverse() // max-heap
+            .then_with(|| self.cost.cmp(&other.cost).reverse())
+            .then_with(|| self.board.cmp(&other.board))
+            .then_with(|| self.last.cmp(&other.last))
+    }
+}
+
+/// The moves leading to each board expanded during a search, stored as parent pointers
+/// so that each board only needs to store its last move.
+#[derive(Debug, Default)]
+struct MoveTree {
+    /// The parent of each board and the move from it, or `None` for the starting board
+    nodes: Vec<Option<(usize, Move)>>,
+}
+
+impl MoveTree {
+    /// Returns the index of the new board
+    fn push(&mut self, last: Option<(usize, Move)>) -> usize {
+        self.nodes.push(last);
+        self.nodes.len() - 1
+    }
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    /// The moves from the starting board to the board at `node`
+    fn moves(&self, mut node: usize) -> Vec<Move> {
+        let mut moves = vec![];
+        while let Some((parent, move_)) = self.nodes[node] {
+            moves.push(move_);
+            node = parent;
+        }
+        moves.reverse();
+        moves
+    }
+    /// The moves from the starting board to the board at `node`, followed by `last`
+    fn moves_to(&self, node: usize, last: Move) -> Vec<Move> {
+        let mut moves = self.moves(node);
+        moves.push(last);
+        moves
+    }
+}
+
+/// A reason why a [`Board`] is not a legal position, see [`Board::validate`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BoardProblem {
+    /// The board has this many columns instead of 6
+    ColumnCount(usize),
+    /// More than 4 columns are solved, but there are only 4 of each card
+    TooManySolved(usize),
+    /// There are `count` of `card` outside of solved columns, instead of `expected`
+    CardCount { card: Card, count: usize, expected: usize },
+    /// The column with this index has a cheat card but no other cards
+    CheatOnEmptyColumn(usize),
+    /// The column with this index has `below_run` cards below the run on top of it. With a full run of 9 on
+    /// top, that would be more cards than a [`PackedBoard`] can hold. No deal leads to more than 6.
+    ColumnTooLong { column: usize, below_run: usize },
+}
+
+impl std::fmt::Display for BoardProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            BoardProblem::ColumnCount(count) => write!(f, "{} columns instead of 6", count),
+            BoardProblem::TooManySolved(count) => write!(f, "{} solved columns, but at most 4 can be solved", count),
+            BoardProblem::CardCount { card, count, expected } => {
+                write!(f, "{} of {} instead of {}", count, card.to_str(), expected)
+            },
+            BoardProblem::CheatOnEmptyColumn(column) => write!(f, "cheat on the empty column {}", column + 1),
+            BoardProblem::ColumnTooLong { column, below_run } => {
+                write!(f, "column {} has {} cards below the run on top of it", column + 1, below_run)
+            },
+        }
+    }
+}
+
+/// The problems which make a [`Board`] an illegal position
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InvalidBoard {
+    pub problems: Vec<BoardProblem>,
+}
+
+impl std::fmt::Display for InvalidBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid board: ")?;
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i > 0 { f.write_str(", ")?; }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidBoard {}
+
+/// Why a list of moves doesn't solve a board, see [`Board::verify_solution`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolutionError {
+    /// Move `index` (counting from 0) can't be made
+    IllegalMove { index: usize, move_: Move, error: MoveError },
+    /// Every move can be made, but the board isn't solved afterwards
+    NotSolved,
+}
+
+impl std::fmt::Display for SolutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolutionError::IllegalMove { index, move_, error } => write!(f, "move {} ({}) is illegal: {}", index + 1, move_, error),
+            SolutionError::NotSolved => f.write_str("the board is not solved after the last move"),
+        }
+    }
+}
+
+impl std::error::Error for SolutionError {}
+
 impl Board {
+    /// Checks that this is a legal position: 6 columns, and 4 of each card in total, where each solved column
+    /// counts as one of each card, and no column that could grow too long to be packed into a [`PackedBoard`].
+    /// The solvers call this first, since they could never solve an illegal position.
+    pub fn validate(&self) -> Result<(), InvalidBoard> {
+        let mut problems = vec![];
+        if self.columns.len() != 6 {
+            problems.push(BoardProblem::ColumnCount(self.columns.len()));
+        }
+        let solved = self.columns.iter().filter(|column| matches!(column, Column::Solved)).count();
+        if solved > 4 {
+            problems.push(BoardProblem::TooManySolved(solved));
+        }
+        let mut counts: BTreeMap<Card, usize> = Card::ALL.iter().map(|&card| (card, 0)).collect();
+        for (i, column) in self.columns.iter().enumerate() {
+            if let Column::Unsolved { cards, cheat } = column {
+                if cards.is_empty() && cheat.is_some() {
+                    problems.push(BoardProblem::CheatOnEmptyColumn(i));
+                }
+                // Cards can only be added to the run on top of a column, and a run has at most 9 cards
+                let run = cards.windows(2).rev().take_while(|w| w[1].goes_on() == Some(w[0])).count() + 1;
+                let below_run = cards.len().saturating_sub(run);
+                if below_run + Column::SOLVED_CARDS.len() > MAX_CARDS {
+                    problems.push(BoardProblem::ColumnTooLong { column: i, below_run });
+                }
+                for card in cards.iter().chain(cheat) {
+                    *counts.get_mut(card).unwrap() += 1;
+                }
+            }
+        }
+        let expected = 4usize.saturating_sub(solved);
+        for (card, count) in counts {
+            if count != expected {
+                problems.push(BoardProblem::CardCount { card, count, expected });
+            }
+        }
+        if problems.is_empty() { Ok(()) } else { Err(InvalidBoard { problems }) }
+    }
+    /// Makes `moves` in order, and checks that they solve the board
+    pub fn verify_solution(&self, moves: &[Move]) -> Result<Board, SolutionError> {
+        let mut board = self.clone();
+        for (index, move_) in moves.iter().enumerate() {
+            board = move_.try_apply(&board).map_err(|error| SolutionError::IllegalMove { index, move_: *move_, error })?;
+        }
+        if !board.is_solved() { return Err(SolutionError::NotSolved); }
+        Ok(board)
+    }
     pub fn is_solved(&self) -> bool {
         for column in self.columns.iter() {
             match column {
                 Column::Solved => {},
-                Column::Unsolved { cards, cheat: None } if &cards[..] == &[] => {}
+                Column::Unsolved { cards, cheat: None } if cards.is_empty() => {}
                 _ => { return false; }
             }
         }
         true
     }
+    /// The board with its columns sorted, so that boards which only differ by the order of their columns
+    /// have the same canonical form.
+    ///
+    /// The solvers only use the canonical form (of a [`PackedBoard`]) as the key of their seen sets. The boards
+    /// they search keep their original column order, so the moves they find never need to be mapped back.
+    pub fn canonical(&self) -> Self {
+        let mut columns = self.columns.clone();
+        columns.sort();
+        Board { columns }
+    }
+    /// Checks that the board is a legal position, and packs it to start a search
+    pub(crate) fn packed_start(&self) -> Result<PackedBoard, InvalidBoard> {
+        self.validate()?;
+        // Validation rejects every board that is too large to pack
+        Ok(PackedBoard::pack(self).expect("valid boards can be packed"))
+    }
     pub fn solve_naive(self) -> Option<(Self, Vec<Move>)> {
-        use crate::moves::*;
-        let mut seen: HashSet<Rc<Self>> = HashSet::new();
+        let start = self.packed_start().ok()?;
+        // Keyed by canonical form, since the order of the columns doesn't matter.
+        // The boards in the queue keep their original column order, so moves don't need to be mapped back.
+        let mut seen: HashSet<PackedBoard> = HashSet::new();
+        let mut tree = MoveTree::default();
         let mut queue = VecDeque::with_capacity(1024);
-        let all_moves = &Move::all_moves();
-        queue.push_back((Rc::new(self), vec![]));
-        while let Some((board, moves)) = queue.pop_front() {
-            if seen.contains(&board) { continue; }
-            seen.insert(Rc::clone(&board));
-            for move_ in all_moves {
-                if let Some(board) = move_.apply(&*board) {
-                    let moves = moves.iter().copied().chain(Some(*move_)).collect();
-                    if board.is_solved() { return Some((board, moves)); }
+        queue.push_back((start, None));
+        while let Some((board, last)) = queue.pop_front() {
+            if !seen.insert(board.canonical()) { continue; }
+            let node = tree.push(last);
+            for move_ in board.possible_moves() {
+                if let Some(board) = move_.apply_packed(&board) {
+                    if board.is_solved() { return Some((board.unpack(), tree.moves_to(node, move_))); }
                     // breadth-first
-                    queue.push_back((Rc::new(board), moves));
+                    queue.push_back((board, Some((node, move_))));
                     // depth-first
-//                    queue.push_front((Rc::new(board), moves));
+//                    queue.push_front((board, Some((node, move_))));
                 }
             }
         }
         None
     }
+    /// Greedy best-first search on [`Board::score`].
     pub fn solve(self) -> Option<(Self, Vec<Move>)> {
-        use crate::moves::*;
-
+        self.solve_with(Score, MoveCount, &SolveOptions::default()).solution()
+    }
+    /// Greedy best-first search, expanding the board with the best (lowest) `heuristic` estimate first,
+    /// and breaking ties by the cheapest path according to `cost_model`.
+    ///
+    /// With the `thread` feature, this uses [`Board::solve_with_threads`] with one thread per CPU.
+    pub fn solve_with<H, C>(self, heuristic: H, cost_model: C, options: &SolveOptions) -> SolveOutcome<C::Cost>
+    where
+        H: Heuristic + Sync,
+        C: CostModel + Sync,
+        H::Estimate: Send,
+        C::Cost: Send,
+    {
+        #[cfg(not(feature = "thread"))]
+        {
+            let start = match self.packed_start() {
+                Ok(start) => start,
+                Err(error) => return SolveOutcome::Invalid(error),
+            };
+            let limits = Limits::new(options);
+            let mut stats = SolveStats::default();
+            if start.is_solved() {
+                let stats = limits.finish(stats);
+                return SolveOutcome::Solved { board: self, moves: vec![], cost: C::Cost::default(), optimal: true, stats };
+            }
+            let mut seen: HashSet<PackedBoard> = HashSet::new();
+            let mut tree = MoveTree::default();
+            let mut queue = BinaryHeap::with_capacity(1024);
+            queue.push(GreedyItem::new(&heuristic, start, C::Cost::default(), None));
+//            let mut counter = 0;
+            while let Some(GreedyItem { cost, board, last, depth, .. }) = queue.pop() {
+                if !seen.insert(board.canonical()) {
+                    stats.duplicates += 1;
+                    continue;
+                }
+                stats.seen = seen.len();
+                let memory = search_memory::<GreedyItem<H::Estimate, C::Cost>>(seen.len(), tree.len(), queue.len());
+                if let Some(limit) = limits.reached(tree.len(), memory) {
+                    return limits.give_up(limit, stats);
+                }
+                let node = tree.push(last);
+                stats.expand(depth, queue.len(), memory);
+                limits.progress(&board, tree.len(), queue.len(), depth);
+//                if counter % 256 == 0 {
+//                    println!("\x1b[H\x1b[2J\x1b[3J{} ({}): \n{}\n{:?}", queue.len(), board.score(), board.unpack().to_grid(), tree.moves(node));
+//                }
+//                counter += 1;
+                for move_ in board.possible_moves() {
+                    if let Some(board) = move_.apply_packed(&board) {
+                        stats.generated += 1;
+                        let cost = cost + cost_model.cost(&move_);
+                        if board.is_solved() {
+                            let moves = tree.moves_to(node, move_);
+                            let stats = limits.finish(stats);
+                            return SolveOutcome::Solved { board: board.unpack(), moves, cost, optimal: false, stats };
+                        }
+                        queue.push(GreedyItem::new(&heuristic, board, cost, Some((node, move_))).deeper(depth));
+                    }
+                }
+            }
+            SolveOutcome::Unsolvable { stats: limits.finish(stats) }
+        }
         #[cfg(feature = "thread")]
-        type Rc<T> = Arc<T>;
+        {
+            self.solve_with_threads(heuristic, cost_model, options, num_cpus::get())
+        }
+    }
+    /// [`Board::solve_with`], using `threads` worker threads.
+    ///
+    /// Each board is owned by one worker, chosen by the hash of its canonical form. Each worker keeps its own
+    /// priority queue, set of seen boards and [`MoveTree`] for the boards it owns, and sends the children of the
+    /// boards it expands to their owners' inboxes. Since only the owner of a board checks whether it has been
+    /// seen, no board is expanded twice, and the only shared locks are the inboxes.
+    #[cfg(feature = "thread")]
+    pub fn solve_with_threads<H, C>(self, heuristic: H, cost_model: C, options: &SolveOptions, threads: usize) -> SolveOutcome<C::Cost>
+    where
+        H: Heuristic + Sync,
+        C: CostModel + Sync,
+        H::Estimate: Send,
+        C::Cost: Send,
+    {
+        use std::hash::BuildHasher;
+
+        type Item<H, C> = GreedyItem<<H as Heuristic>::Estimate, <C as CostModel>::Cost>;
+        // Boards sent to a thread, and a condition variable to wake it when it is sent boards or the search finishes
+        type Inbox<H, C> = (Mutex<Vec<Item<H, C>>>, Condvar);
+
+        let start = match self.packed_start() {
+            Ok(start) => start,
+            Err(error) => return SolveOutcome::Invalid(error),
+        };
+        let limits = Limits::new(options);
+        let threads = threads.max(1);
+        if start.is_solved() {
+            let stats = limits.finish(SolveStats::default());
+            return SolveOutcome::Solved { board: self, moves: vec![], cost: C::Cost::default(), optimal: true, stats };
+        }
+        let hasher = std::collections::hash_map::RandomState::new();
+        let owner = |board: &PackedBoard| (hasher.hash_one(board.canonical()) % threads as u64) as usize;
+        let inboxes: Vec<Inbox<H, C>> = (0..threads).map(|_| Default::default()).collect();
+        inboxes[owner(&start)].0.lock().unwrap().push(GreedyItem::new(&heuristic, start, C::Cost::default(), None));
+        // The number of items that have been sent but not fully expanded yet. The children of a board are
+        // counted before the board itself is uncounted, so this only reaches 0 once the search space is exhausted.
+        let pending = AtomicUsize::new(1);
+        let expanded = AtomicUsize::new(0);
+        let finished = AtomicBool::new(false);
+        let finish = || {
+            finished.store(true, Ordering::SeqCst);
+            for (inbox, wake) in &inboxes {
+                // Taking the lock means that a thread about to wait is either already waiting, or will see `finished`
+                drop(inbox.lock().unwrap());
+                wake.notify_all();
+            }
+        };
+        let gave_up: Mutex<Option<Limit>> = Mutex::new(None);
+        // The solved board, its cost, the node in the tree of its parent, and the last move
+        type Solution<C> = (PackedBoard, C, usize, Move);
+        let result: Mutex<Option<Solution<C::Cost>>> = Mutex::new(None);
+        let worker = |thread: usize| {
+            let mut seen: HashSet<PackedBoard> = HashSet::new();
+            // Node `i` of this thread's tree is node `i * threads + thread` globally
+            let mut tree = MoveTree::default();
+            let mut queue = BinaryHeap::with_capacity(1024);
+            let mut outboxes: Vec<Vec<Item<H, C>>> = (0..threads).map(|_| vec![]).collect();
+            let mut stats = SolveStats::default();
+            while !finished.load(Ordering::SeqCst) {
+                let (inbox, wake) = &inboxes[thread];
+                let mut inbox = inbox.lock().unwrap();
+                // Sleep until another thread sends some boards, or the search finishes
+                while queue.is_empty() && inbox.is_empty() && !finished.load(Ordering::SeqCst) {
+                    inbox = wake.wait(inbox).unwrap();
+                }
+                queue.extend(inbox.drain(..));
+                drop(inbox);
+                let Some(GreedyItem { cost, board, last, depth, .. }) = queue.pop() else { continue; };
+                if !seen.insert(board.canonical()) {
+                    stats.duplicates += 1;
+                } else {
+                    stats.seen = seen.len();
+                    // Claim this expansion first, so that threads can't race past the node limit together
+                    let total = expanded.fetch_add(1, Ordering::SeqCst);
+                    // Each board is only in one thread's seen set and tree, and every pending item is in some
+                    // thread's queue or inbox
+                    let memory = search_memory::<Item<H, C>>(total, total, pending.load(Ordering::Relaxed));
+                    if let Some(limit) = limits.reached(total, memory) {
+                        gave_up.lock().unwrap().get_or_insert(limit);
+                        finish();
+                        return (tree, stats);
+                    }
+                    let total = total + 1;
+                    let node = tree.push(last) * threads + thread;
+                    stats.expand(depth, queue.len(), search_memory::<Item<H, C>>(seen.len(), tree.len(), queue.len()));
+                    // Every pending item is in some thread's queue or inbox
+                    limits.progress(&board, total, pending.load(Ordering::Relaxed), depth);
+                    for move_ in board.possible_moves() {
+                        if let Some(board) = move_.apply_packed(&board) {
+                            stats.generated += 1;
+                            let cost = cost + cost_model.cost(&move_);
+                            if board.is_solved() {
+                                result.lock().unwrap().get_or_insert((board, cost, node, move_));
+                                finish();
+                                return (tree, stats);
+                            }
+                            pending.fetch_add(1, Ordering::SeqCst);
+                            outboxes[owner(&board)].push(GreedyItem::new(&heuristic, board, cost, Some((node, move_))).deeper(depth));
+                        }
+                    }
+                    for ((inbox, wake), outbox) in inboxes.iter().zip(&mut outboxes) {
+                        if !outbox.is_empty() {
+                            inbox.lock().unwrap().append(outbox);
+                            wake.notify_one();
+                        }
+                    }
+                }
+                if pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    // Every board has been expanded, so the board is unsolvable
+                    finish();
+                }
+            }
+            (tree, stats)
+        };
+        let (trees, stats): (Vec<MoveTree>, Vec<SolveStats>) = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|thread| scope.spawn(move || worker(thread))).collect();
+            workers.into_iter().map(|worker| worker.join().unwrap()).unzip()
+        });
+        let stats = limits.finish(stats.iter().fold(SolveStats::default(), |mut total, stats| {
+            total.merge(stats);
+            total
+        }));
+        let Some((board, cost, mut node, last)) = result.into_inner().unwrap() else {
+            return match gave_up.into_inner().unwrap() {
+                Some(limit) => SolveOutcome::GaveUp { limit, stats },
+                None => SolveOutcome::Unsolvable { stats },
+            };
+        };
+        let mut moves = vec![last];
+        while let Some((parent, move_)) = trees[node % threads].nodes[node / threads] {
+            moves.push(move_);
+            node = parent;
+        }
+        moves.reverse();
+        SolveOutcome::Solved { board: board.unpack(), moves, cost, optimal: false, stats }
+    }
+    /// Finds a solution with the minimum number of moves, using A* with [`Board::lower_bound`].
+    ///
+    /// A returned solution is always proven to be optimal, i.e. no shorter solution exists.
+    pub fn solve_optimal(self) -> Option<(Self, Vec<Move>)> {
+        match self.solve_optimal_with(LowerBound, MoveCount, &SolveOptions::default()) {
+            SolveOutcome::Solved { board, moves, .. } => Some((board, moves)),
+            _ => None,
+        }
+    }
+    /// Finds a solution with the fewest [`Move::Cheat`]s, and among those the fewest moves.
+    ///
+    /// The returned [`CheatCost`] is proven to be minimal, i.e. no solution with fewer cheats exists.
+    pub fn solve_min_cheats(self) -> Option<(Self, Vec<Move>, CheatCost)> {
+        match self.solve_optimal_with(CheatLowerBound, CheatsThenMoves, &SolveOptions::default()) {
+            SolveOutcome::Solved { board, moves, cost, .. } => Some((board, moves, cost)),
+            _ => None,
+        }
+    }
+    /// A* search, returning a solution with the minimum total cost according to `cost_model`.
+    ///
+    /// `heuristic` must be consistent with `cost_model` (it may never overestimate the remaining cost,
+    /// and may not decrease by more than the cost of a move), so that the first time a board is popped
+    /// it has been reached by a cheapest path. A solution found this way therefore always has
+    /// [`SolveOutcome::Solved::optimal`] set.
+    pub fn solve_optimal_with<H, C>(self, heuristic: H, cost_model: C, options: &SolveOptions) -> SolveOutcome<C::Cost>
+    where
+        H: Heuristic<Estimate = C::Cost>,
+        C: CostModel,
+    {
+        use crate::moves::*;
 
         #[derive(Debug, Clone, PartialEq, Eq)]
-        struct QueueItem {
-            board: Rc<Board>,
-            moves: Vec<Move>,
+        struct QueueItem<C> {
+            estimate: C,
+            cost: C,
+            board: PackedBoard,
+            /// The index of the parent board in the [`MoveTree`], and the move from it to this board
+            last: Option<(usize, Move)>,
+            /// The number of moves to this board
+            depth: usize,
         }
 
-        impl std::cmp::PartialOrd for QueueItem {
+        impl<C: Ord> std::cmp::PartialOrd for QueueItem<C> {
             fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
                 Some(self.cmp(other))
             }
         }
 
-        impl std::cmp::Ord for QueueItem {
+        impl<C: Ord> std::cmp::Ord for QueueItem<C> {
             fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-                self.board.score().cmp(&other.board.score()).reverse() // max-heap
-                    .then_with(|| self.moves.len().cmp(&other.moves.len()).reverse())
+                self.estimate.cmp(&other.estimate).reverse() // max-heap
+                    // Among equal estimates, prefer the costlier node, since it is closer to a solution
+                    .then_with(|| self.cost.cmp(&other.cost))
                     .then_with(|| self.board.cmp(&other.board))
-                    .then_with(|| self.moves.cmp(&other.moves))
+                    .then_with(|| self.last.cmp(&other.last))
             }
         }
 
-        impl From<(Rc<Board>, Vec<Move>)> for QueueItem {
-            fn from((board, moves): (Rc<Board>, Vec<Move>)) -> Self {
-                Self { board, moves }
+        let start = match self.packed_start() {
+            Ok(start) => start,
+            Err(error) => return SolveOutcome::Invalid(error),
+        };
+        let limits = Limits::new(options);
+        let mut stats = SolveStats::default();
+        let mut seen: HashSet<PackedBoard> = HashSet::new();
+        let mut tree = MoveTree::default();
+        let mut queue = BinaryHeap::<QueueItem<C::Cost>>::with_capacity(1024);
+        queue.push(QueueItem { estimate: heuristic.estimate_packed(&start), cost: C::Cost::default(), board: start, last: None, depth: 0 });
+        while let Some(QueueItem { cost, board, last, depth, .. }) = queue.pop() {
+            // Duplicates were already counted when generated, so this was queued twice before either was expanded
+            if !seen.insert(board.canonical()) {
+                stats.stale += 1;
+                continue;
             }
-        }
-
-        impl From<QueueItem> for (Rc<Board>, Vec<Move>) {
-            fn from(QueueItem { board, moves }: QueueItem) -> Self {
-                (board, moves)
+            stats.seen = seen.len();
+            if board.is_solved() {
+                let moves = last.map_or(vec![], |(parent, move_)| tree.moves_to(parent, move_));
+                let stats = limits.finish(stats);
+                return SolveOutcome::Solved { board: board.unpack(), moves, cost, optimal: true, stats };
             }
-        }
-        #[cfg(not(feature = "thread"))]
-        {
-            let mut seen: HashSet<Rc<Self>> = HashSet::new();
-            let mut queue = BinaryHeap::<QueueItem>::with_capacity(1024);
-            let all_moves = &Move::all_moves();
-            queue.push((Rc::new(self), vec![]).into());
-//            let mut counter = 0;
-            while let Some((board, moves)) = queue.pop().map(Into::into) {
-                if seen.contains(&board) { continue; }
-                seen.insert(Rc::clone(&board));
-//                if counter % 256 == 0 {
-//                    println!("\x1b[H\x1b[2J\x1b[3J{} ({}): \n{}\n{:?}", queue.len(), board.score(), board.to_string(), moves);
-//                }
-//                counter += 1;
-                for move_ in all_moves { //board.possible_moves() {
-                    if let Some(board) = move_.apply(&*board) {
-                        let moves = moves.iter().copied().chain(Some(*move_)).collect();
-                        if board.is_solved() { return Some((board, moves)); }
-                        queue.push((Rc::new(board), moves).into());
+            let memory = search_memory::<QueueItem<C::Cost>>(seen.len(), tree.len(), queue.len());
+            if let Some(limit) = limits.reached(tree.len(), memory) {
+                return limits.give_up(limit, stats);
+            }
+            let node = tree.push(last);
+            stats.expand(depth, queue.len(), memory);
+            limits.progress(&board, tree.len(), queue.len(), depth);
+            for move_ in board.possible_moves() {
+                if let Some(board) = move_.apply_packed(&board) {
+                    stats.generated += 1;
+                    if seen.contains(&board.canonical()) {
+                        stats.duplicates += 1;
+                        continue;
                     }
+                    let cost = cost + cost_model.cost(&move_);
+                    let estimate = cost + heuristic.estimate_packed(&board);
+                    queue.push(QueueItem { estimate, cost, board, last: Some((node, move_)), depth: depth + 1 });
                 }
             }
         }
-        #[cfg(feature = "thread")]
-        {
-            let seen: Arc<RwLock<HashSet<Rc<Self>>>> = Arc::new(RwLock::new(HashSet::new()));
-            let queue: Arc<Mutex<BinaryHeap<QueueItem>>> = Arc::new(Mutex::new({
-                let mut queue = BinaryHeap::<QueueItem>::with_capacity(1024);
-                queue.push((Rc::new(self), vec![]).into());
-                queue
-            }));
-            let result: Arc<Mutex<Option<(Self, Vec<Move>)>>> = Arc::new(Mutex::new(None));
-            let finished: Arc<AtomicBool> = Arc::new(false.into());
-            let all_moves = Arc::new(Move::all_moves());
-            let make_worker = |thread| {
-                let seen = Arc::clone(&seen);
-                let queue = Arc::clone(&queue);
-                let result = Arc::clone(&result);
-                let finished = Arc::clone(&finished);
-                let all_moves = Arc::clone(&all_moves);
-                move || {
-//                    let mut counter = 0;
-                    while !finished.load(Ordering::Relaxed) {
-                        let top = { queue.lock().unwrap().pop().map(Into::into) };
-                        if let Some((board, moves)) = top {
-                            if seen.read().unwrap().contains(&board) { continue; }
-                            seen.write().unwrap().insert(Rc::clone(&board));
-//                            if counter % 256 == 0 {
-//                                let queue = queue.lock().unwrap();
-//                                println!("\x1b[H\x1b[2J\x1b[3J{} ({}): \n{}\n{:?}", queue.len(), board.score(), board.to_string(), moves);
-//                            }
-//                            counter += 1;
-                            for move_ in &**all_moves {
-                                if let Some(board) = move_.apply(&*board) {
-                                    let moves = moves.iter().copied().chain(Some(*move_)).collect();
-                                    if board.is_solved() {
-                                        finished.store(true, Ordering::Relaxed);
-                                        *result.lock().unwrap() = Some((board, moves));
-                                        return;
-                                    }
-                                    queue.lock().unwrap().push((Rc::new(board), moves).into());
-                                }
-                            }
-                        } else {
-                            std::thread::sleep(std::time::Duration::from_millis(10));
-                        }
+        SolveOutcome::Unsolvable { stats: limits.finish(stats) }
+    }
+    /// Exhaustive depth-first search, which only stores the canonical [`PackedBoard`] of each board seen,
+    /// and the moves not yet tried from each board on the current path.
+    ///
+    /// Returns the first solution found, which is usually far from the shortest, with its number of moves as
+    /// its cost. [`SolveOutcome::Unsolvable`] proves that no reachable board is solved, and its `stats.seen`
+    /// is the number of distinct boards reachable from this one (up to the order of the columns).
+    pub fn solve_exhaustive(self, options: &SolveOptions) -> SolveOutcome<usize> {
+        /// The unseen children of `board`, with the most promising last, or the move that solves it
+        fn children(board: &PackedBoard, seen: &HashSet<PackedBoard>, stats: &mut SolveStats) -> Result<Vec<Move>, Move> {
+            let mut children = vec![];
+            for move_ in board.possible_moves() {
+                if let Some(board) = move_.apply_packed(board) {
+                    stats.generated += 1;
+                    if board.is_solved() { return Err(move_); }
+                    if seen.contains(&board.canonical()) {
+                        stats.duplicates += 1;
+                        continue;
                     }
+                    children.push((board.score(), move_));
                 }
-            };
-            let workers: Vec<_> = (0..num_cpus::get()).map(|thread| std::thread::spawn(make_worker(thread))).collect();
-            workers.into_iter().for_each(|t| t.join().unwrap());
-            return Arc::try_unwrap(result).unwrap().into_inner().unwrap();
+            }
+            children.sort_unstable_by(|a, b| b.cmp(a));
+            Ok(children.into_iter().map(|(_, move_)| move_).collect())
         }
-        None
-    }
-    /// Lower is better
-    pub fn score(&self) -> i64 {
-        let mut score = 0;
-        for column in self.columns.iter() {
-            match column {
-                Column::Solved => {
-                    score -= 1024;
-                },
-                Column::Unsolved { cards, cheat: None } => {
-                    let mut depth = 1;
-                    for w in cards.windows(2).rev() {
-                        if let [c1, c2] = w {
-                            if c2.goes_on() == Some(*c1) { depth += 1; }
-                        }
-                    }
-                    score -= depth * depth;
+
+        let start = match self.packed_start() {
+            Ok(start) => start,
+            Err(error) => return SolveOutcome::Invalid(error),
+        };
+        let limits = Limits::new(options);
+        let mut stats = SolveStats::default();
+        if start.is_solved() {
+            let stats = limits.finish(stats);
+            return SolveOutcome::Solved { board: self, moves: vec![], cost: 0, optimal: true, stats };
+        }
+        let mut seen: HashSet<PackedBoard> = HashSet::from([start.canonical()]);
+        // The moves to the board being expanded
+        let mut path: Vec<Move> = vec![];
+        // The boards on the path, with the moves from them that haven't been tried yet
+        let mut stack: Vec<(PackedBoard, Vec<Move>)> = vec![];
+        // The number of moves in `stack`
+        let mut pending = 0;
+        let mut board = start;
+        loop {
+            stats.expanded += 1;
+            stats.seen = seen.len();
+            stats.max_depth = stats.max_depth.max(path.len());
+            limits.progress(&board, stats.expanded, pending, path.len());
+            match children(&board, &seen, &mut stats) {
+                Ok(moves) => {
+                    pending += moves.len();
+                    stack.push((board, moves));
                 },
-                Column::Unsolved { cards, cheat: Some(_) } => {
-                    // Intentionally different from no cheat, since having a cheat on top of a lot of cards is bad(?)
-                    score += (cards.len() * cards.len()) as i64;
-                    score += 256;
+                Err(move_) => {
+                    let board = move_.apply_packed(&board).unwrap().unpack();
+                    path.push(move_);
+                    let stats = limits.finish(stats);
+                    return SolveOutcome::Solved { board, cost: path.len(), moves: path, optimal: false, stats };
                 },
             }
+            stats.peak_queue = stats.peak_queue.max(pending);
+            // Each board on the stack also has a move in `path`
+            let memory = memory(
+                seen.len(),
+                pending * std::mem::size_of::<Move>() + stack.len() * std::mem::size_of::<(PackedBoard, Vec<Move>, Move)>(),
+            );
+            stats.peak_memory = stats.peak_memory.max(memory);
+            // Backtrack to the deepest board with an unseen child
+            board = loop {
+                let Some((parent, moves)) = stack.last_mut() else {
+                    return SolveOutcome::Unsolvable { stats: limits.finish(stats) };
+                };
+                let Some(move_) = moves.pop() else {
+                    stack.pop();
+                    path.pop();
+                    continue;
+                };
+                pending -= 1;
+                let child = move_.apply_packed(parent).unwrap();
+                // Seen since its parent was expanded
+                if !seen.insert(child.canonical()) {
+                    stats.stale += 1;
+                    continue;
+                }
+                path.push(move_);
+                break child;
+            };
+            if let Some(limit) = limits.reached(stats.expanded, memory) {
+                stats.seen = seen.len();
+                return limits.give_up(limit, stats);
+            }
         }
-        score
     }
+    /// An admissible (and consistent) lower bound on the number of moves needed to solve the board.
+    ///
+    /// Every run of cards that sits on a card it doesn't go on (including a T that isn't at the bottom
+    /// of its column), and every bottom card that isn't a T, must be moved at least once, each by a
+    /// different move. Every cheat card must be moved off of its column.
+    pub fn lower_bound(&self) -> usize {
+        self.columns.iter().map(|column| column.view().lower_bound()).sum()
+    }
+    /// Lower is better
+    pub fn score(&self) -> i64 {
+        self.columns.iter().map(|column| column.view().score()).sum()
+    }
+    /// All moves for which [`Move::apply`] would succeed.
     pub fn possible_moves(&self) -> Vec<Move> {
-        let mut moves = Vec::with_capacity(100);
-        for (from, from_col) in self.columns.iter().enumerate() {
-            match from_col {
-                Column::Solved => {},
-                Column::Unsolved { cheat: Some(cheat), .. } => {
-                    for (to, to_col) in self.columns.iter().enumerate() {
-                        match to_col {
-                            Column::Solved => {},
-                            Column::Unsolved { cheat: Some(_), .. } => {},
-                            Column::Unsolved { cards: to_cards, cheat: None } => {
-                                if &to_cards[..] == &[] || to_cards.last() == Some(cheat) {
-                                    moves.push(Move::UnCheat { from, to });
-                                }
-                            },
-                        };
-                    }
-                },
-                Column::Unsolved { cards: from_cards, cheat: None } => {
-                    for (to, to_col) in self.columns.iter().enumerate() {
-                        match to_col {
-                            Column::Solved => {},
-                            Column::Unsolved { cheat: Some(_), .. } => {},
-                            Column::Unsolved { cards: to_cards, cheat: None } => {
-                                // TODO
-                                moves.push(Move::Cheat { from, to });
-                                moves.extend((1..=9).map(|count: usize| Move::Normal { from, to, count: NonZeroUsize::try_from(count).unwrap() }));
-                            },
-                        };
-                    }
-                },
-            };
-        }
-        moves
+        let columns: Vec<ColumnView> = self.columns.iter().map(Column::view).collect();
+        possible_moves(&columns)
     }
-    pub fn to_string(&self) -> String {
+    /// The board drawn with the columns side by side, bottom card first, and cheat cards highlighted with
+    /// ANSI escape codes. See the [`Display`](std::fmt::Display) impl for a notation that can be parsed.
+    pub fn to_grid(&self) -> String {
         let mut columns: Vec<Box<dyn Iterator<Item=&'static str>>> = self.columns.iter().map(
             |column| -> Box<dyn Iterator<Item=&'static str>> { match column {
                 Column::Solved => Box::new(std::iter::once("S")),
@@ -353,7 +939,7 @@ impl Board {
                     if image.width() < x + card_image.width() { continue; }
                     if image.height() < y + card_image.height() { continue; }
                     let sub_image = image.view(x, y, card_image.width(), card_image.height());
-                    if images_same(&sub_image, &*card_image) {
+                    if images_same(&sub_image, card_image) {
                         found.insert((x, y), *card);
                     }
                 }
@@ -366,7 +952,7 @@ impl Board {
         let y_values: BTreeSet<u32> = y_values.into_iter().take(6).collect(); // take(6) to ignore numbers on the bottom of cards, since the values are sorted top->bottom
         let x_values: BTreeSet<u32> = found.iter().filter_map(
             // Ensure that the bottom number on the top card is not counted
-            |((x, y), _card)| if y_values.contains(&y) { Some(*x) } else { None }
+            |((x, y), _card)| if y_values.contains(y) { Some(*x) } else { None }
         ).collect();
         let mut columns: [Vec<Card>; 6] = [(); 6].map(|_| Vec::with_capacity(6));
         for y_value in y_values {
@@ -377,8 +963,411 @@ impl Board {
         }
         Some(Board {
             columns: columns.into_iter().map(
-                |cards| Column::Unsolved { cards: cards.into(), cheat: None }
+                |cards| Column::Unsolved { cards, cheat: None }
             ).collect()
         })
     }
 }
+
+/// One column per line, bottom card first, using the glyphs of [`Card::to_str`]. A cheat card follows a `*`,
+/// a solved column is `S` and an empty column is `-`. For example, a column with a 7 cheated onto a K on a T,
+/// a solved column and an empty column:
+///
+/// ```text
+/// TK*7
+/// S
+/// -
+/// ```
+///
+/// When parsing, spaces and blank lines are ignored.
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 { f.write_str("\n")?; }
+            match column {
+                Column::Solved => f.write_str("S")?,
+                Column::Unsolved { cards, cheat: None } if cards.is_empty() => f.write_str("-")?,
+                Column::Unsolved { cards, cheat } => {
+                    for card in cards {
+                        f.write_str(card.to_str())?;
+                    }
+                    if let Some(cheat) = cheat {
+                        write!(f, "*{}", cheat.to_str())?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`Board`] couldn't be parsed. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseBoardError {
+    /// The line has a character that isn't a card
+    UnknownCard { line: usize, glyph: char },
+    /// The line has a `*` that isn't followed by exactly one card
+    BadCheat { line: usize },
+}
+
+impl std::fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            ParseBoardError::UnknownCard { line, glyph } => write!(f, "line {}: {:?} is not a card", line, glyph),
+            ParseBoardError::BadCheat { line } => write!(f, "line {}: a cheat (*) must be followed by exactly one card", line),
+        }
+    }
+}
+
+impl std::error::Error for ParseBoardError {}
+
+impl std::str::FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let glyphs: String = line.chars().filter(|glyph| !glyph.is_whitespace()).collect();
+            let cards = |glyphs: &str| glyphs.chars()
+                .map(|glyph| Card::from_char(glyph).ok_or(ParseBoardError::UnknownCard { line: line_number, glyph }))
+                .collect::<Result<Vec<Card>, _>>();
+            columns.push(match &*glyphs {
+                "" => continue,
+                "S" => Column::Solved,
+                "-" => Column::Unsolved { cards: vec![], cheat: None },
+                glyphs => match glyphs.split_once('*') {
+                    None => Column::Unsolved { cards: cards(glyphs)?, cheat: None },
+                    Some((glyphs, cheat)) => match cards(cheat)?[..] {
+                        [cheat] => Column::Unsolved { cards: cards(glyphs)?, cheat: Some(cheat) },
+                        _ => return Err(ParseBoardError::BadCheat { line: line_number }),
+                    },
+                },
+            });
+        }
+        Ok(Board { columns })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// xorshift64*, so that the test doesn't need a dependency on `rand`
+    pub(crate) struct Rng(pub(crate) u64);
+
+    impl Rng {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+        pub(crate) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    pub(crate) fn random_board(rng: &mut Rng) -> Board {
+        Board::deal(rng.next())
+    }
+
+    /// The boards on a random walk of up to `steps` moves from a random deal, starting with the deal, so that
+    /// boards with cheats, empty and solved columns are also checked
+    pub(crate) fn random_walk(rng: &mut Rng, steps: usize) -> Vec<Board> {
+        let mut boards = vec![random_board(rng)];
+        for _ in 0..steps {
+            let board = boards.last().unwrap();
+            let moves = board.possible_moves();
+            if moves.is_empty() { break; }
+            let next = moves[rng.below(moves.len())].apply(board).unwrap();
+            boards.push(next);
+        }
+        boards
+    }
+
+    /// Deals that are known to be solvable, each with a quick search
+    pub(crate) fn solvable_deals() -> impl Iterator<Item = Board> {
+        [1, 2, 3].into_iter().map(Board::deal)
+    }
+
+    /// Makes `moves` in order, each of which must be legal
+    pub(crate) fn play(board: Board, moves: &[Move]) -> Board {
+        moves.iter().try_fold(board, |board, move_| move_.apply(&board)).unwrap()
+    }
+
+    #[test]
+    fn possible_moves_matches_all_moves() {
+        let all_moves = Move::all_moves();
+        let mut rng = Rng(0x0123_4567_89AB_CDEF);
+        for _ in 0..200 {
+            for board in random_walk(&mut rng, 100) {
+                let mut expected: Vec<Move> = all_moves.iter().copied()
+                    .filter(|move_| move_.apply(&board).is_some())
+                    .collect();
+                let mut actual = board.possible_moves();
+                expected.sort();
+                actual.sort();
+                assert_eq!(actual, expected, "{:?}", board);
+            }
+        }
+    }
+
+    #[test]
+    fn solutions_are_valid() {
+        for board in solvable_deals() {
+            let (solved, moves) = board.clone().solve().unwrap();
+            assert!(solved.is_solved());
+            assert_eq!(board.verify_solution(&moves), Ok(solved));
+            let (solved, optimal_moves) = board.clone().solve_optimal().unwrap();
+            assert_eq!(board.verify_solution(&optimal_moves), Ok(solved));
+            assert!(board.lower_bound() <= optimal_moves.len());
+            assert!(optimal_moves.len() <= moves.len());
+            let (solved, moves) = board.clone().solve_exhaustive(&SolveOptions::default()).solution().unwrap();
+            assert_eq!(board.verify_solution(&moves), Ok(solved));
+        }
+    }
+
+    /// Only 201 distinct boards are reachable from this board, none of which are solved
+    fn unsolvable_board() -> Board {
+        use Card::*;
+        Board { columns: vec![
+            Column::Solved,
+            Column::Solved,
+            Column::Solved,
+            Column::Unsolved { cards: vec![K, Six, Ten], cheat: None },
+            Column::Unsolved { cards: vec![Eight, D, T, V, Seven, Nine], cheat: None },
+            Column::Unsolved { cards: vec![], cheat: None },
+        ] }
+    }
+
+    #[test]
+    fn unsolvable_returns_none() {
+        let board = unsolvable_board();
+        assert_eq!(board.clone().solve(), None);
+        assert_eq!(board.clone().solve_naive(), None);
+        assert_eq!(board.clone().solve_optimal(), None);
+        assert_eq!(board.clone().solve_min_cheats(), None);
+        match board.solve_exhaustive(&SolveOptions::default()) {
+            SolveOutcome::Unsolvable { stats } => assert_eq!(stats.seen, 201),
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "thread")]
+    fn threads_share_the_search() {
+        // More threads than the machine may have CPUs, so that boards are always passed between threads
+        let threads = 4;
+        let options = SolveOptions::default();
+        for number in 1..=10 {
+            let board = Board::deal(number);
+            let (solved, moves) = board.clone().solve_with_threads(Score, MoveCount, &options, threads).solution().unwrap();
+            assert_eq!(board.verify_solution(&moves), Ok(solved));
+        }
+        match unsolvable_board().solve_with_threads(Score, MoveCount, &options, threads) {
+            SolveOutcome::Unsolvable { stats } => assert_eq!(stats.seen, 201),
+            outcome => panic!("{:?}", outcome),
+        }
+        let options = SolveOptions { max_nodes: Some(10), ..SolveOptions::default() };
+        match Board::deal(1).solve_with_threads(Score, MoveCount, &options, threads) {
+            SolveOutcome::GaveUp { limit: Limit::Nodes, stats } => assert_eq!(stats.expanded, 10),
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn invalid_boards_are_rejected() {
+        use Card::*;
+        for number in 0..20 {
+            assert_eq!(Board::deal(number).validate(), Ok(()));
+        }
+        let mut board = Board::deal(1);
+        board.columns.pop();
+        board.columns[0] = Column::Unsolved { cards: vec![], cheat: Some(Six) };
+        board.columns[1] = Column::Solved;
+        let problems = board.validate().unwrap_err().problems;
+        assert!(problems.contains(&BoardProblem::ColumnCount(5)));
+        assert!(problems.contains(&BoardProblem::CheatOnEmptyColumn(0)));
+        assert!(problems.iter().any(|problem| matches!(problem, BoardProblem::CardCount { expected: 3, .. })));
+        assert!(matches!(board.clone().solve_optimal_with(LowerBound, MoveCount, &SolveOptions::default()), SolveOutcome::Invalid(_)));
+        assert_eq!(board.solve_naive(), None);
+        let board = Board { columns: vec![Column::Solved; 6] };
+        assert_eq!(board.validate().unwrap_err().problems, vec![BoardProblem::TooManySolved(6)]);
+        // All of the cards in one column, which would overflow a packed board
+        let mut board = Board::deal(1);
+        let cards = board.columns.iter_mut().flat_map(|column| match column {
+            Column::Unsolved { cards, .. } => std::mem::take(cards),
+            Column::Solved => vec![],
+        }).collect();
+        board.columns[0] = Column::Unsolved { cards, cheat: None };
+        let problems = board.validate().unwrap_err().problems;
+        assert!(matches!(problems[..], [BoardProblem::ColumnTooLong { column: 0, .. }]), "{:?}", problems);
+        assert!(matches!(board.solve_optimal_with(LowerBound, MoveCount, &SolveOptions::default()), SolveOutcome::Invalid(_)));
+    }
+
+    #[test]
+    fn move_errors() {
+        use Card::*;
+        let board = Board { columns: vec![
+            Column::Unsolved { cards: vec![T, Nine, Eight, Seven], cheat: None },
+            Column::Unsolved { cards: vec![V, Ten], cheat: None },
+            Column::Unsolved { cards: vec![], cheat: None },
+            Column::Unsolved { cards: vec![K], cheat: Some(Six) },
+            Column::Solved,
+            Column::Unsolved { cards: vec![Eight], cheat: None },
+        ] };
+        let error = |move_: &str| move_.parse::<Move>().unwrap().try_apply(&board).unwrap_err();
+        assert_eq!(error("1>7"), MoveError::NoSuchColumn(6));
+        assert_eq!(error("1>1"), MoveError::SameColumn(0));
+        assert_eq!(error("5>3"), MoveError::ColumnSolved(4));
+        assert_eq!(error("3>1"), MoveError::EmptyColumn(2));
+        assert_eq!(error("2>3x3"), MoveError::NotEnoughCards { column: 1, count: 2 });
+        assert_eq!(error("1>3x4"), MoveError::NotARun { card: Nine, on: T });
+        assert_eq!(error("1>2"), MoveError::DoesNotGoOn { card: Seven, onto: Ten });
+        assert_eq!(error("4>3"), MoveError::CheatInTheWay(3));
+        assert_eq!(error("1>4"), MoveError::CheatInTheWay(3));
+        assert_eq!(error("1c3"), MoveError::CheatOntoEmpty(2));
+        assert_eq!(error("1c6"), MoveError::CheatOntoMatching { card: Seven, onto: Eight });
+        assert_eq!(error("1u2"), MoveError::NoCheat(0));
+        assert_eq!(error("4u2"), MoveError::DoesNotGoOn { card: Six, onto: Ten });
+        assert!("4u1".parse::<Move>().unwrap().try_apply(&board).is_ok());
+        assert_eq!(
+            board.verify_solution(&["4u1".parse().unwrap(), "2>3".parse().unwrap()]),
+            Err(SolutionError::NotSolved),
+        );
+        assert!(matches!(
+            board.verify_solution(&["4u1".parse().unwrap(), "1>2".parse().unwrap()]),
+            Err(SolutionError::IllegalMove { index: 1, .. }),
+        ));
+    }
+
+    #[test]
+    fn move_notation_round_trips() {
+        for move_ in Move::all_moves() {
+            assert_eq!(move_.to_string().parse::<Move>(), Ok(move_));
+        }
+        assert_eq!("2>5x3".parse::<Move>(), Ok(Move::Normal { from: 1, to: 4, count: NonZeroUsize::new(3).unwrap() }));
+        for invalid in ["", "2>", ">5", "0>5", "2>5x0", "2x5", "2c5x2", "a>b"] {
+            assert!(invalid.parse::<Move>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn notation_round_trips() {
+        use Card::*;
+        let mut rng = Rng(0x3C3C_A5A5_0F0F_5A5A);
+        for _ in 0..100 {
+            for board in random_walk(&mut rng, 100) {
+                assert_eq!(board.to_string().parse::<Board>(), Ok(board.clone()));
+            }
+        }
+        let board = Board { columns: vec![
+            Column::Unsolved { cards: vec![T, K], cheat: Some(Seven) },
+            Column::Solved,
+            Column::Unsolved { cards: vec![], cheat: None },
+            Column::Unsolved { cards: vec![Six, Ten, V], cheat: None },
+        ] };
+        assert_eq!(board.to_string(), "TK*7\nS\n-\n6XV");
+        assert_eq!("\n TK * 7\n\nS\n-\n6 X V\n".parse::<Board>(), Ok(board));
+        assert_eq!("TK\n6Y".parse::<Board>(), Err(ParseBoardError::UnknownCard { line: 2, glyph: 'Y' }));
+        assert_eq!("TK*".parse::<Board>(), Err(ParseBoardError::BadCheat { line: 1 }));
+        assert_eq!("TK*76".parse::<Board>(), Err(ParseBoardError::BadCheat { line: 1 }));
+    }
+
+    #[test]
+    fn limits_give_up() {
+        let board = random_board(&mut Rng(0x2468_ACE0_1357_9BDF));
+        let options = SolveOptions { max_nodes: Some(10), ..SolveOptions::default() };
+        match board.clone().solve_optimal_with(LowerBound, MoveCount, &options) {
+            SolveOutcome::GaveUp { limit: Limit::Nodes, stats } => {
+                assert_eq!(stats.expanded, 10);
+                assert!(stats.generated >= stats.duplicates + stats.stale && stats.seen <= stats.expanded + 1);
+            },
+            outcome => panic!("{:?}", outcome),
+        }
+        let options = SolveOptions { max_memory: Some(0), ..SolveOptions::default() };
+        assert!(matches!(
+            board.solve_with(Score, MoveCount, &options),
+            SolveOutcome::GaveUp { limit: Limit::Memory, .. },
+        ));
+    }
+
+    #[test]
+    fn cancel_and_progress() {
+        use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+        let board = random_board(&mut Rng(0x2468_ACE0_1357_9BDF));
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let options = SolveOptions { cancel: Some(cancel), ..SolveOptions::default() };
+        assert!(matches!(
+            board.clone().solve_with(Score, MoveCount, &options),
+            SolveOutcome::GaveUp { limit: Limit::Cancelled, .. },
+        ));
+        // Cancel from the progress callback
+        let cancel = CancelToken::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let options = SolveOptions {
+            cancel: Some(cancel.clone()),
+            progress: Some(ProgressCallback::new({
+                let calls = calls.clone();
+                move |progress| {
+                    assert!(progress.expanded > 0 && progress.best_score < i64::MAX);
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    cancel.cancel();
+                }
+            })),
+            ..SolveOptions::default()
+        };
+        match board.solve_optimal_with(LowerBound, MoveCount, &options) {
+            SolveOutcome::GaveUp { limit: Limit::Cancelled, .. } => assert_eq!(calls.load(Ordering::SeqCst), 1),
+            // Solved before the first progress report
+            SolveOutcome::Solved { .. } => assert_eq!(calls.load(Ordering::SeqCst), 0),
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn moves_are_explained() {
+        use Card::*;
+        let board = Board { columns: vec![
+            Column::Unsolved { cards: vec![T, Nine, Eight, Seven], cheat: None },
+            Column::Unsolved { cards: vec![V, Ten], cheat: None },
+            Column::Unsolved { cards: vec![], cheat: None },
+            Column::Unsolved { cards: vec![K], cheat: Some(Six) },
+        ] };
+        let explain = |move_: Move| move_.explain(&board);
+        let count = |count| NonZeroUsize::new(count).unwrap();
+        assert_eq!(
+            explain(Move::Normal { from: 0, to: 1, count: count(3) }).unwrap(),
+            "move the 3-card run 9-8-7 from column 1 onto the X in column 2",
+        );
+        assert_eq!(
+            explain(Move::Normal { from: 0, to: 2, count: count(1) }).unwrap(),
+            "move the 7 from column 1 into the empty column 3",
+        );
+        assert_eq!(explain(Move::Cheat { from: 1, to: 0 }).unwrap(), "cheat the X from column 2 onto the 7 in column 1");
+        assert_eq!(explain(Move::UnCheat { from: 3, to: 0 }).unwrap(), "move the cheated 6 from column 4 onto the 7 in column 1");
+        assert_eq!(explain(Move::Normal { from: 0, to: 1, count: count(4) }), None);
+    }
+
+    #[test]
+    fn canonical_ignores_column_order() {
+        let mut rng = Rng(0x0F1E_2D3C_4B5A_6978);
+        for _ in 0..200 {
+            let board = random_walk(&mut rng, 20).pop().unwrap();
+            let mut shuffled = board.clone();
+            for i in (1..shuffled.columns.len()).rev() {
+                shuffled.columns.swap(i, rng.below(i + 1));
+            }
+            let canonical = board.canonical();
+            assert_eq!(shuffled.canonical(), canonical);
+            assert_eq!(PackedBoard::pack(&shuffled).unwrap().canonical(), PackedBoard::pack(&canonical).unwrap());
+            // Boards that are the same up to column order have the same children up to column order
+            let children = |board: &Board| board.possible_moves().iter()
+                .map(|move_| move_.apply(board).unwrap().canonical())
+                .collect::<BTreeSet<Board>>();
+            assert_eq!(children(&shuffled), children(&board));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::cards::*;
+
+/// The pseudo-random number generator used to shuffle deals (SplitMix64).
+///
+/// Deals are identified by their number, so the output of this must never change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DealRng(u64);
+
+impl DealRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+impl Board {
+    /// Deal number `number`, which is the same on every machine and in every version.
+    ///
+    /// The deck (4 of each card, in the order of [`Card::ALL`]) is shuffled by a Fisher-Yates shuffle using
+    /// [`DealRng::new(number)`](DealRng::new), and dealt into six columns of six cards, bottom card first.
+    pub fn deal(number: u64) -> Self {
+        let mut rng = DealRng::new(number);
+        let mut deck: Vec<Card> = Card::ALL.iter().flat_map(|&card| [card; 4]).collect();
+        for i in (1..deck.len()).rev() {
+            deck.swap(i, rng.below(i + 1));
+        }
+        Board {
+            columns: deck.chunks(6).map(
+                |cards| Column::Unsolved { cards: cards.to_vec(), cheat: None }
+            ).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deals_are_reproducible() {
+        for number in 0..100 {
+            let board = Board::deal(number);
+            assert_eq!(board, Board::deal(number));
+            assert_eq!(board.columns.len(), 6);
+            let mut cards: Vec<Card> = board.columns.iter().flat_map(|column| match column {
+                Column::Unsolved { cards, cheat: None } if cards.len() == 6 => cards.clone(),
+                column => panic!("{:?}", column),
+            }).collect();
+            cards.sort();
+            assert_eq!(cards, Card::ALL.iter().flat_map(|&card| [card; 4]).collect::<Vec<Card>>());
+        }
+        assert_ne!(Board::deal(1), Board::deal(2));
+        // Deals must never change
+        use Card::*;
+        assert_eq!(Board::deal(1).columns[0], Column::Unsolved { cards: vec![Seven, D, K, Ten, Six, Eight], cheat: None });
+        assert_eq!(Board::deal(1).columns[5], Column::Unsolved { cards: vec![V, T, Nine, T, D, V], cheat: None });
+    }
+}
@@ -0,0 +1,114 @@
+use crate::analysis::*;
+use crate::cards::*;
+use crate::deal::*;
+use crate::heuristics::*;
+use crate::solver::*;
+
+/// A property for [`generate`] to look for in a deal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// The fewest cheats in any solution is exactly this
+    Cheats(usize),
+    /// The shortest solution has at least this many moves
+    MinMoves(usize),
+    /// The [`Difficulty::score`] is at least this
+    MinDifficulty(f64),
+}
+
+impl Target {
+    /// How far `board` is from having this property, or `None` if it can't be solved within the limits.
+    /// Solving `board` verifies that it has the property when this returns `Some(0.0)`.
+    pub fn distance(&self, board: &Board, options: &SolveOptions) -> Option<f64> {
+        match *self {
+            Target::Cheats(cheats) => match board.clone().solve_optimal_with(CheatLowerBound, CheatsThenMoves, options) {
+                SolveOutcome::Solved { cost, .. } => Some(cost.cheats.abs_diff(cheats) as f64),
+                _ => None,
+            },
+            Target::MinMoves(moves) => match board.clone().solve_optimal_with(LowerBound, MoveCount, options) {
+                SolveOutcome::Solved { moves: solution, .. } => Some(moves.saturating_sub(solution.len()) as f64),
+                _ => None,
+            },
+            Target::MinDifficulty(score) => {
+                let difficulty = Difficulty::new(board, options).ok()?;
+                Some((score - difficulty.score()).max(0.0))
+            },
+        }
+    }
+}
+
+/// How many mutations without getting closer to the target before starting again from a new deal
+const RESTART_AFTER: usize = 20;
+
+/// Looks for a deal with the `target` property, trying at most `attempts` candidates.
+///
+/// Starting from [`Board::deal(seed)`](Board::deal), each candidate swaps two cards of the best deal so far,
+/// and is kept if it is at least as close to the target. If that doesn't get closer for a while, the search
+/// starts again from a new random deal. The same `seed` always gives the same result.
+pub fn generate(target: Target, seed: u64, attempts: usize, options: &SolveOptions) -> Option<Board> {
+    let mut rng = DealRng::new(seed);
+    // The deal being improved, and its distance from the target
+    let mut current: Option<(Board, f64)> = None;
+    let mut stale = 0;
+    for attempt in 0..attempts {
+        let candidate = match &current {
+            Some((board, _)) => {
+                let mut board = board.clone();
+                swap_cards(&mut board, &mut rng);
+                board
+            },
+            None if attempt == 0 => Board::deal(seed),
+            None => Board::deal(rng.next_u64()),
+        };
+        let distance = target.distance(&candidate, options);
+        if distance == Some(0.0) {
+            return Some(candidate);
+        }
+        match (distance, &current) {
+            (Some(distance), None) => current = Some((candidate, distance)),
+            (Some(distance), Some((_, current_distance))) if distance < *current_distance => {
+                current = Some((candidate, distance));
+                stale = 0;
+            },
+            // Moving sideways helps to get off of plateaus
+            (Some(distance), Some((_, current_distance))) if distance == *current_distance => {
+                current = Some((candidate, distance));
+                stale += 1;
+            },
+            _ => stale += 1,
+        }
+        if stale >= RESTART_AFTER {
+            current = None;
+            stale = 0;
+        }
+    }
+    None
+}
+
+/// Swaps two random cards in different columns
+fn swap_cards(board: &mut Board, rng: &mut DealRng) {
+    let columns = board.columns.len();
+    let from = rng.below(columns);
+    let to = (from + 1 + rng.below(columns - 1)) % columns;
+    let [Column::Unsolved { cards: from_cards, .. }, Column::Unsolved { cards: to_cards, .. }] =
+        board.columns.get_disjoint_mut([from, to]).unwrap() else { return; };
+    if from_cards.is_empty() || to_cards.is_empty() { return; }
+    let (i, j) = (rng.below(from_cards.len()), rng.below(to_cards.len()));
+    std::mem::swap(&mut from_cards[i], &mut to_cards[j]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_deals_have_target_property() {
+        // Skip candidates that are slow to verify
+        let options = SolveOptions { max_nodes: Some(5_000), ..SolveOptions::default() };
+        let board = generate(Target::MinMoves(36), 1, 200, &options).unwrap();
+        let (_, moves) = board.clone().solve_optimal().unwrap();
+        assert!(moves.len() >= 36);
+        let board = generate(Target::Cheats(0), 1, 200, &options).unwrap();
+        let (_, _, cost) = board.solve_min_cheats().unwrap();
+        assert_eq!(cost.cheats, 0);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::cards::*;
+use crate::moves::*;
+use crate::packed::*;
+
+/// Estimates how far a board is from being solved. Lower is better.
+///
+/// Any `Fn(&Board) -> E` can be used as a heuristic.
+pub trait Heuristic {
+    type Estimate: Ord;
+    fn estimate(&self, board: &Board) -> Self::Estimate;
+    /// The solvers store boards packed. Override this if the estimate can be computed
+    /// without unpacking the board onto the heap.
+    fn estimate_packed(&self, board: &PackedBoard) -> Self::Estimate {
+        self.estimate(&board.unpack())
+    }
+}
+
+impl<F, E> Heuristic for F
+where
+    F: Fn(&Board) -> E,
+    E: Ord,
+{
+    type Estimate = E;
+    fn estimate(&self, board: &Board) -> E {
+        self(board)
+    }
+}
+
+/// The cost of making a move. The cost of a solution is the sum of the costs of its moves.
+///
+/// Any `Fn(&Move) -> C` can be used as a cost model.
+pub trait CostModel {
+    type Cost: Copy + Ord + Default + std::ops::Add<Output = Self::Cost>;
+    fn cost(&self, move_: &Move) -> Self::Cost;
+}
+
+impl<F, C> CostModel for F
+where
+    F: Fn(&Move) -> C,
+    C: Copy + Ord + Default + std::ops::Add<Output = C>,
+{
+    type Cost = C;
+    fn cost(&self, move_: &Move) -> C {
+        self(move_)
+    }
+}
+
+/// [`Board::score`]. Not admissible, so only useful for [`Board::solve_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Score;
+
+impl Heuristic for Score {
+    type Estimate = i64;
+    fn estimate(&self, board: &Board) -> i64 {
+        board.score()
+    }
+    fn estimate_packed(&self, board: &PackedBoard) -> i64 {
+        board.score()
+    }
+}
+
+/// [`Board::lower_bound`]. Admissible and consistent for [`MoveCount`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LowerBound;
+
+impl Heuristic for LowerBound {
+    type Estimate = usize;
+    fn estimate(&self, board: &Board) -> usize {
+        board.lower_bound()
+    }
+    fn estimate_packed(&self, board: &PackedBoard) -> usize {
+        board.lower_bound()
+    }
+}
+
+/// [`Board::lower_bound`] moves and no cheats. Admissible and consistent for [`CheatsThenMoves`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheatLowerBound;
+
+impl Heuristic for CheatLowerBound {
+    type Estimate = CheatCost;
+    fn estimate(&self, board: &Board) -> CheatCost {
+        CheatCost { cheats: 0, moves: board.lower_bound() }
+    }
+    fn estimate_packed(&self, board: &PackedBoard) -> CheatCost {
+        CheatCost { cheats: 0, moves: board.lower_bound() }
+    }
+}
+
+/// Every move costs 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveCount;
+
+impl CostModel for MoveCount {
+    type Cost = usize;
+    fn cost(&self, _move: &Move) -> usize {
+        1
+    }
+}
+
+/// The cost of a sequence of moves, ordered by the number of [`Move::Cheat`]s first,
+/// then by the total number of moves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CheatCost {
+    pub cheats: usize,
+    pub moves: usize,
+}
+
+impl std::ops::Add for CheatCost {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            cheats: self.cheats + other.cheats,
+            moves: self.moves + other.moves,
+        }
+    }
+}
+
+/// Fewest cheats first, then fewest moves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheatsThenMoves;
+
+impl CostModel for CheatsThenMoves {
+    type Cost = CheatCost;
+    fn cost(&self, move_: &Move) -> CheatCost {
+        CheatCost { cheats: move_.is_cheat().into(), moves: 1 }
+    }
+}
+
+/// A separate weight for each kind of move.
+///
+/// For use with [`Board::solve_optimal_with`], [`LowerBound`] multiplied by the cost of the cheapest
+/// possible move is admissible and consistent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weighted {
+    /// The cost of a [`Move::Normal`], plus `per_card` for each card moved.
+    pub normal: usize,
+    pub per_card: usize,
+    pub cheat: usize,
+    pub uncheat: usize,
+}
+
+impl Default for Weighted {
+    fn default() -> Self {
+        Self { normal: 1, per_card: 0, cheat: 1, uncheat: 1 }
+    }
+}
+
+impl CostModel for Weighted {
+    type Cost = usize;
+    fn cost(&self, move_: &Move) -> usize {
+        match *move_ {
+            Move::Normal { count, .. } => self.normal + self.per_card * count.get(),
+            Move::Cheat { .. } => self.cheat,
+            Move::UnCheat { .. } => self.uncheat,
+        }
+    }
+}
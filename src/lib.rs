@@ -0,0 +1,16 @@
+#[cfg(feature = "image")]
+extern crate image_ as image;
+
+pub mod cards;
+pub mod moves;
+pub mod heuristics;
+pub mod packed;
+pub mod solver;
+pub mod solutions;
+pub mod analysis;
+pub mod deal;
+pub mod survey;
+pub mod generate;
+pub mod record;
+#[cfg(feature = "serde")]
+pub mod report;
@@ -1,11 +1,231 @@
 #[cfg(feature = "image")]
 extern crate image_ as image;
 
-pub mod cards;
-pub mod moves;
+use molek_syntez_solitaire::{analysis, cards, generate, heuristics, moves, record, solutions, solver, survey};
+
+/// Prints what is wrong with the command line, and exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+/// `count` followed by `noun`, pluralised unless `count` is 1
+fn plural<T: std::fmt::Display + PartialEq + From<u8>>(count: T, noun: &str) -> String {
+    let suffix = if count == T::from(1) { "" } else { "s" };
+    format!("{} {}{}", count, noun, suffix)
+}
+
+/// Parses the value following `flag`
+fn value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    args.next().and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage_error(&format!("{} requires a number", flag)))
+}
+
+/// Prints the outcome of solving `board` as a [`report::SolveReport`] in JSON, with the board after each move
+/// if `with_boards`
+#[cfg(feature = "serde")]
+fn print_json<C>(board: cards::Board, outcome: solver::SolveOutcome<C>, with_boards: bool) {
+    let report = molek_syntez_solitaire::report::SolveReport::new(board, outcome, with_boards);
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json<C>(_board: cards::Board, _outcome: solver::SolveOutcome<C>, _with_boards: bool) {
+    eprintln!("--json requires the serde feature");
+    std::process::exit(1);
+}
+
+/// Prints the outcome of solving `board`, with a solution as a game record which is also saved to `save`.
+/// With `json`, prints it as JSON instead, with the board after each move if `json` is `Some(true)`.
+fn print_outcome<C>(board: cards::Board, outcome: solver::SolveOutcome<C>, show_stats: bool, save: Option<&str>, json: Option<bool>) {
+    use solver::*;
+    if let Some(path) = save {
+        match &outcome {
+            SolveOutcome::Solved { moves, .. } => {
+                let record = record::GameRecord::new(board.clone(), moves.clone());
+                if let Err(error) = std::fs::write(path, format!("{}\n", record)) {
+                    eprintln!("Could not save the solution to {}: {}", path, error);
+                    std::process::exit(1);
+                }
+            },
+            _ => eprintln!("No solution to save, so {} was not written", path),
+        }
+    }
+    if let Some(with_boards) = json {
+        print_json(board, outcome, with_boards);
+        return;
+    }
+    println!();
+    println!();
+    if show_stats {
+        println!("{}", outcome.stats());
+        println!();
+    }
+    match outcome {
+        SolveOutcome::Solved { moves, optimal, .. } => {
+            let cheats = moves.iter().filter(|move_| move_.is_cheat()).count();
+            println!("Solved in {} with {}{}:", plural(moves.len(), "move"), plural(cheats, "cheat"), if optimal { " (optimal)" } else { "" });
+            println!();
+            println!("{}", record::GameRecord::new(board, moves));
+        },
+        SolveOutcome::Unsolvable { stats } => {
+            println!("Could not solve: none of the {} reachable boards are solved", stats.seen);
+        },
+        SolveOutcome::GaveUp { limit, stats } => {
+            println!(
+                "Gave up ({:?} limit reached) after expanding {} boards ({} distinct) in {:.3?}",
+                limit, stats.expanded, stats.seen, stats.elapsed,
+            );
+        },
+        SolveOutcome::Invalid(error) => {
+            println!("Could not solve: {}", error);
+        },
+    }
+}
+
+/// Prints why a search didn't find a solution
+fn print_solve_error(error: solver::SolveError) {
+    use solver::SolveError;
+    match error {
+        SolveError::Unsolvable => println!("Could not solve"),
+        SolveError::GaveUp(limit) => println!("Gave up ({:?} limit reached)", limit),
+        SolveError::Invalid(error) => println!("Could not solve: {}", error),
+    }
+}
+
+/// Reads the text in `path` (or stdin if `path` is `-`), or `None` if the file isn't text
+fn read_text(path: &str) -> Option<String> {
+    use std::io::Read;
+    let read = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    match read {
+        Ok(text) => Some(text),
+        Err(error) if error.kind() == std::io::ErrorKind::InvalidData => None,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", path, error);
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Reads a board from the screenshot in `path`
+#[cfg(feature = "image")]
+fn read_screenshot(path: &str) -> cards::Board {
+    let image = image::open(path).unwrap_or_else(|error| {
+        eprintln!("Could not read {} as a board or a screenshot: {}", path, error);
+        std::process::exit(1);
+    });
+    cards::Board::from_image(image.into_luma8()).unwrap_or_else(|| {
+        eprintln!("Could not recognise the board in {}", path);
+        std::process::exit(1);
+    })
+}
+
+#[cfg(not(feature = "image"))]
+fn read_screenshot(path: &str) -> cards::Board {
+    eprintln!("{} is not a text file, and reading screenshots requires the image feature", path);
+    std::process::exit(1);
+}
+
+/// Reads a board in the text notation from `path` (or from stdin if `path` is `-`), or from a screenshot if
+/// the file isn't text
+fn read_board(path: &str) -> cards::Board {
+    let Some(text) = read_text(path) else {
+        return read_screenshot(path);
+    };
+    text.parse().unwrap_or_else(|error| {
+        eprintln!("Invalid board: {}", error);
+        std::process::exit(1);
+    })
+}
+
+/// Makes the moves of the game record in `path` (or stdin if `path` is `-`), printing the board after each
+fn replay(path: &str) {
+    let Some(text) = read_text(path) else {
+        eprintln!("Invalid game record: {} is not a text file", path);
+        std::process::exit(1);
+    };
+    let record: record::GameRecord = text.parse().unwrap_or_else(|error| {
+        eprintln!("Invalid game record: {}", error);
+        std::process::exit(1);
+    });
+    if let Err(error) = record.board.validate() {
+        eprintln!("Invalid game record: {}", error);
+        std::process::exit(1);
+    }
+    let mut board = record.board.clone();
+    println!("{}", board.to_grid());
+    for (i, &move_) in record.moves.iter().enumerate() {
+        println!();
+        match move_.try_apply(&board) {
+            Ok(next) => {
+                let explanation = move_.explain(&board).unwrap();
+                println!("{}. {}: {}", i + 1, move_, explanation);
+                board = next;
+                println!("{}", board.to_grid());
+            },
+            Err(error) => {
+                println!("{}. {} is illegal: {}", i + 1, move_, error);
+                std::process::exit(1);
+            },
+        }
+    }
+    println!();
+    if board.is_solved() {
+        println!("Solved in {}", plural(record.moves.len(), "move"));
+    } else {
+        println!("Not solved after {}", plural(record.moves.len(), "move"));
+        std::process::exit(1);
+    }
+}
+
+/// Reads moves from stdin and makes them, printing the board after each
+fn play(mut board: cards::Board, options: &solver::SolveOptions) {
+    use std::io::Write;
+    // The boards before each move, for undo
+    let mut history = vec![];
+    println!("Enter moves like 2>5, 2>5x3 (3 cards), 1c4 (cheat) or 4u2 (move a cheat card), or hint, undo or quit");
+    loop {
+        println!("{}", board.to_grid());
+        if board.is_solved() {
+            println!("Solved in {}", plural(history.len(), "move"));
+            return;
+        }
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).unwrap() == 0 { return; }
+        match line.trim() {
+            "" => {},
+            "quit" => return,
+            "undo" => match history.pop() {
+                Some(previous) => board = previous,
+                None => println!("Nothing to undo"),
+            },
+            "hint" => match analysis::hint(&board, false, options) {
+                analysis::Hint::Move { move_, remaining, .. } => {
+                    println!("{}: {}, then {}", move_, move_.explain(&board).unwrap(), plural(remaining, "more move"));
+                },
+                hint => println!("{:?}", hint),
+            },
+            line => match line.parse::<moves::Move>() {
+                Ok(move_) => match move_.try_apply(&board) {
+                    Ok(next) => history.push(std::mem::replace(&mut board, next)),
+                    Err(error) => println!("Can't make move {}: {}", move_, error),
+                },
+                Err(error) => println!("{}", error),
+            },
+        }
+    }
+}
 
 fn main() {
     use cards::*;
+    use heuristics::*;
+    use solver::*;
     use Card::*;
 //    let board = Board { columns: vec![
 //        Column::Unsolved {
@@ -33,12 +253,91 @@ fn main() {
 //            cheat: None,
 //        },
 //    ] };
-    let image_name = std::env::args().nth(1);
-    let board = match image_name {
-        Some(image_name) => Board::from_image(
-            image::open(image_name).unwrap().into_luma8()
-        ).unwrap(),
-        None => Board { columns: vec![
+    enum Mode {
+        Greedy,
+        Optimal,
+        MinCheats,
+        Exhaustive,
+        CountSolutions,
+        Analyze,
+    }
+    let mut hint = false;
+    let mut survey = None;
+    let mut difficulty = false;
+    let mut interactive = false;
+    let mut replaying = false;
+    let mut save = None;
+    let mut json = None;
+    let mut target = None;
+    let mut attempts = 1000;
+    let mut mode = Mode::Greedy;
+    let mut options = SolveOptions::default();
+    let mut show_stats = false;
+    let mut deal = None;
+    let mut input = None;
+    let mut args = std::env::args().skip(1).peekable();
+    // A subcommand must be the first argument, so a board file named e.g. `hint` is still read as a board
+    const SUBCOMMANDS: [&str; 6] = ["hint", "difficulty", "play", "replay", "generate", "survey"];
+    match args.next_if(|arg| SUBCOMMANDS.contains(&arg.as_str())).as_deref() {
+        Some("hint") => hint = true,
+        Some("difficulty") => difficulty = true,
+        Some("play") => interactive = true,
+        Some("replay") => replaying = true,
+        Some("generate") => target = Some(match args.next().as_deref() {
+            Some("cheats") => generate::Target::Cheats(value(&mut args, "cheats")),
+            Some("moves") => generate::Target::MinMoves(value(&mut args, "moves")),
+            Some("difficulty") => generate::Target::MinDifficulty(value(&mut args, "difficulty")),
+            _ => usage_error("generate requires a target: cheats N, moves N or difficulty N"),
+        }),
+        Some("survey") => survey = Some(value::<u64>(&mut args, "survey")),
+        _ => {},
+    }
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--optimal" => mode = Mode::Optimal,
+            "--min-cheats" => mode = Mode::MinCheats,
+            "--prove" => mode = Mode::Exhaustive,
+            "--deal" => deal = Some(value(&mut args, &arg)),
+            "--count-solutions" => mode = Mode::CountSolutions,
+            "--analyze" => mode = Mode::Analyze,
+            "--json" => json = Some(false),
+            "--json-boards" => json = Some(true),
+            "--save" => save = Some(args.next().unwrap_or_else(|| usage_error("--save requires a file name"))),
+            "--attempts" => attempts = value(&mut args, &arg),
+            "--time-limit" => {
+                let secs = value(&mut args, &arg);
+                options.time_limit = Some(std::time::Duration::try_from_secs_f64(secs)
+                    .unwrap_or_else(|_| usage_error(&format!("{} requires a number", arg))));
+            },
+            "--max-nodes" => options.max_nodes = Some(value(&mut args, &arg)),
+            "--max-memory" => options.max_memory = Some(value::<usize>(&mut args, &arg) << 20),
+            "--stats" => show_stats = true,
+            "--progress" => {
+                options.progress = Some(ProgressCallback::new(|progress| eprintln!(
+                    "{:.1?}: expanded {} boards, {} queued, best score {}, depth {}",
+                    progress.elapsed, progress.expanded, progress.queue_len, progress.best_score, progress.depth,
+                )));
+                options.progress_interval = std::time::Duration::from_secs(1);
+            },
+            _ => input = Some(arg),
+        }
+    }
+    // Only solving prints a `SolveReport`
+    let not_solving = hint || difficulty || interactive || replaying || survey.is_some() || target.is_some()
+        || matches!(mode, Mode::CountSolutions | Mode::Analyze);
+    if json.is_some() && not_solving {
+        eprintln!("--json and --json-boards only work when solving a board, not with a subcommand, --count-solutions or --analyze");
+        std::process::exit(2);
+    }
+    if replaying {
+        replay(input.as_deref().expect("replay requires a game record"));
+        return;
+    }
+    let board = match (deal, input) {
+        (Some(_), Some(input)) => usage_error(&format!("Give either --deal or a board, not both (got board {})", input)),
+        (Some(deal), None) => Board::deal(deal),
+        (None, Some(input)) => read_board(&input),
+        (None, None) => Board { columns: vec![
             Column::Unsolved {
                 cards: (&[Eight, Seven, K, V, K, Six][..]).into(),
                 cheat: None,
@@ -65,21 +364,100 @@ fn main() {
             },
         ] }
     };
-    match board.solve() {
-        Some((_board, moves)) => {
-            println!("");
-            println!("");
-            println!("Solved: [");
-            let moves = IntoIterator::into_iter(moves);
-            for r#move in moves {
-                println!("\t{:?}", r#move);
+    if let Err(error) = board.validate() {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    if let Some(count) = survey {
+        // Deals are numbered from `--deal`, or 1
+        let first = deal.unwrap_or(1);
+        let deals: Vec<u64> = (first..first + count).collect();
+        let results = survey::survey(&deals, &options);
+        survey::write_csv(&results, std::io::stdout().lock()).unwrap();
+        let summary = survey::Summary::new(&results);
+        eprintln!("{}", summary);
+        eprintln!();
+        summary.write_csv(std::io::stderr().lock()).unwrap();
+        return;
+    }
+    if let Some(target) = target {
+        // Candidates are generated from `--deal`, or 1
+        match generate::generate(target, deal.unwrap_or(1), attempts, &options) {
+            // The board goes to stdout in the text notation, so it can be saved and solved, and the grid to stderr
+            Some(board) => {
+                eprintln!("{}", board.to_grid());
+                println!("{}", board);
+            },
+            None => {
+                eprintln!("No deal found after {} attempts", attempts);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if interactive {
+        play(board, &options);
+        return;
+    }
+    if difficulty {
+        match analysis::Difficulty::new(&board, &options) {
+            Ok(difficulty) => println!("{}", difficulty),
+            Err(error) => print_solve_error(error),
+        }
+        return;
+    }
+    if hint {
+        let min_cheats = matches!(mode, Mode::MinCheats);
+        match analysis::hint(&board, min_cheats, &options) {
+            analysis::Hint::Move { move_, remaining, cheats } => {
+                let explanation = move_.explain(&board).unwrap();
+                println!("{}{} ({})", explanation[..1].to_uppercase(), &explanation[1..], move_);
+                println!("Then {} with {}", plural(remaining, "more move"), plural(cheats, "cheat"));
+            },
+            analysis::Hint::Solved => println!("Already solved"),
+            analysis::Hint::Unsolvable => println!("Could not solve"),
+            analysis::Hint::Unknown(limit) => println!("Gave up ({:?} limit reached)", limit),
+            analysis::Hint::Invalid(error) => println!("Could not solve: {}", error),
+        }
+        return;
+    }
+    match mode {
+        Mode::Greedy => {
+            let outcome = board.clone().solve_with(Score, MoveCount, &options);
+            print_outcome(board, outcome, show_stats, save.as_deref(), json);
+        },
+        Mode::Optimal => {
+            let outcome = board.clone().solve_optimal_with(LowerBound, MoveCount, &options);
+            print_outcome(board, outcome, show_stats, save.as_deref(), json);
+        },
+        Mode::MinCheats => {
+            let outcome = board.clone().solve_optimal_with(CheatLowerBound, CheatsThenMoves, &options);
+            print_outcome(board, outcome, show_stats, save.as_deref(), json);
+        },
+        Mode::Exhaustive => {
+            let outcome = board.clone().solve_exhaustive(&options);
+            print_outcome(board, outcome, show_stats, save.as_deref(), json);
+        },
+        Mode::CountSolutions => match solutions::MinimalSolutions::new(&board, &options) {
+            Ok(solutions) => {
+                println!("{} in {}", plural(solutions.total(), "solution"), plural(solutions.solution_len(), "move"));
+                for moves in solutions.take(10) {
+                    let moves: Vec<String> = moves.iter().map(|move_| move_.to_string()).collect();
+                    println!("\t{}", moves.join(" "));
+                }
+            },
+            Err(error) => print_solve_error(error),
+        },
+        Mode::Analyze => {
+            println!("{}", board.to_grid());
+            let analyses = match analysis::analyze(&board, &options) {
+                Ok(analyses) => analyses,
+                Err(error) => return println!("Could not solve: {}", error),
+            };
+            let width = analyses.iter().map(|analysis| analysis.move_.to_string().len()).max().unwrap_or(0);
+            for analysis in analyses {
+                println!("{:width$}  {}", analysis.move_.to_string(), analysis.solvability, width = width);
             }
-            println!("]");
         },
-        None => {
-            println!("");
-            println!("");
-            println!("Could not solve");
-        }
-    };
+    }
 }
@@ -1,7 +1,11 @@
 use crate::cards::*;
+use crate::packed::*;
 use std::num::NonZeroUsize;
 
+/// Columns are indices into [`Board::columns`], so they count from 0
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum Move {
     Normal { from: usize, to: usize, count: NonZeroUsize },
     Cheat { from: usize, to: usize },
@@ -9,6 +13,9 @@ pub enum Move {
 }
 
 impl Move {
+    pub fn is_cheat(&self) -> bool {
+        matches!(self, Move::Cheat { .. })
+    }
     pub fn all_moves() -> Vec<Move> {
         use Move::*;
         let position_pairs: Vec<(usize, usize)> =
@@ -29,91 +36,209 @@ impl Move {
                     .map(|(from, to)| UnCheat { from, to })
             ).collect()
     }
+    /// Like [`Move::apply`], but without unpacking the board onto the heap.
+    ///
+    /// Also returns `None` if the resulting board would be too large to pack,
+    /// which can't happen for a legal position.
+    pub fn apply_packed(&self, board: &PackedBoard) -> Option<PackedBoard> {
+        board.unpacked().apply(self)
+    }
+    /// Describes this move in words, numbering the columns from 1, e.g.
+    /// "move the 3-card run 9-8-7 from column 2 onto the X in column 5".
+    ///
+    /// Returns `None` if this move can't be made on `board`.
+    pub fn explain(&self, board: &Board) -> Option<String> {
+        use Move::*;
+        self.apply(board)?;
+        let cards = |column: usize| match &board.columns[column] {
+            Column::Unsolved { cards, cheat } => (&cards[..], *cheat),
+            Column::Solved => (&[][..], None),
+        };
+        let destination = |to: usize| match cards(to).0.last() {
+            Some(card) => format!("onto the {} in column {}", card.to_str(), to + 1),
+            None => format!("into the empty column {}", to + 1),
+        };
+        Some(match *self {
+            Normal { from, to, count } if count.get() == 1 => {
+                let card = cards(from).0.last()?;
+                format!("move the {} from column {} {}", card.to_str(), from + 1, destination(to))
+            },
+            Normal { from, to, count } => {
+                let (cards, _) = cards(from);
+                let run: Vec<&str> = cards[cards.len() - count.get()..].iter().map(Card::to_str).collect();
+                format!("move the {}-card run {} from column {} {}", count, run.join("-"), from + 1, destination(to))
+            },
+            Cheat { from, to } => {
+                let card = cards(from).0.last()?;
+                format!("cheat the {} from column {} {}", card.to_str(), from + 1, destination(to))
+            },
+            UnCheat { from, to } => {
+                let card = cards(from).1?;
+                format!("move the cheated {} from column {} {}", card.to_str(), from + 1, destination(to))
+            },
+        })
+    }
     pub fn apply(&self, board: &Board) -> Option<Board> {
+        self.try_apply(board).ok()
+    }
+    /// Like [`Move::apply`], but says why the move can't be made.
+    pub fn try_apply(&self, board: &Board) -> Result<Board, MoveError> {
         use Move::*;
-        fn get_mut_two<'a, T>(slice: &'a mut [T], i1: usize, i2: usize) -> Option<(&'a mut T, &'a mut T)> {
-            if i1 == i2 { return None; }
-            let a = slice as *mut [T];
-            unsafe { Some((
-                (*a).get_mut(i1)?,
-                (*a).get_mut(i2)?,
-            )) }
+        use MoveError::*;
+        let (from, to) = match *self {
+            Normal { from, to, .. } | Cheat { from, to } | UnCheat { from, to } => (from, to),
+        };
+        for column in [from, to] {
+            if column >= board.columns.len() { return Err(NoSuchColumn(column)); }
         }
+        if from == to { return Err(SameColumn(from)); }
         let mut board = board.clone();
-        let mut board = match *self {
-            Normal { from, to, count } => {
-//                let from = board.columns.get_mut(from)?;
-//                let to = board.columns.get_mut(to)?;
-                {
-                    let (from, to) = get_mut_two(&mut board.columns, from, to)?;
-                    let from = match from {
-                        Column::Unsolved { ref mut cards, cheat: None } => cards,
-                        _ => return None,
-                    };
-                    let to = match to {
-                        Column::Unsolved { ref mut cards, cheat: None } => cards,
-                        _ => return None,
-                    };
-                    let range = from.len().checked_sub(count.into())?..;
-                    let to_be_moved = from.drain(range);
-                    for pair in to_be_moved.as_slice().windows(2) {
-                        if pair[1].goes_on() != Some(pair[0]) { return None; }
-                    }
-                    let goes_on = to.last().copied();
-                    // Any card can be placed on empty column
-                    if goes_on != None && goes_on != to_be_moved.as_slice()[0].goes_on() { return None; }
-                    to.extend_from_slice(to_be_moved.as_slice());
+        let [from_column, to_column] = board.columns.get_disjoint_mut([from, to]).unwrap();
+        let Column::Unsolved { cards: from_cards, cheat: from_cheat } = from_column else { return Err(ColumnSolved(from)); };
+        let Column::Unsolved { cards: to_cards, cheat: to_cheat } = to_column else { return Err(ColumnSolved(to)); };
+        // Nothing can be placed on a cheat card
+        if to_cheat.is_some() { return Err(CheatInTheWay(to)); }
+        match *self {
+            Normal { count, .. } => {
+                // Nothing can be moved out from under a cheat card
+                if from_cheat.is_some() { return Err(CheatInTheWay(from)); }
+                if from_cards.is_empty() { return Err(EmptyColumn(from)); }
+                let start = from_cards.len().checked_sub(count.get())
+                    .ok_or(NotEnoughCards { column: from, count: from_cards.len() })?;
+                let run = &from_cards[start..];
+                if let Some(pair) = run.windows(2).find(|pair| pair[1].goes_on() != Some(pair[0])) {
+                    return Err(NotARun { card: pair[1], on: pair[0] });
                 }
-                Some(board)
+                // Any card can be placed on an empty column
+                if let Some(&top) = to_cards.last() {
+                    if run[0].goes_on() != Some(top) { return Err(DoesNotGoOn { card: run[0], onto: top }); }
+                }
+                to_cards.extend(from_cards.drain(start..));
             },
-            Cheat { from, to } => {
-                {
-                    let (from, to) = get_mut_two(&mut board.columns, from, to)?;
-                    let from = match from {
-                        Column::Unsolved { ref mut cards, cheat: None } => cards,
-                        _ => return None,
-                    };
-                    let (to, to_cheat) = match to {
-                        Column::Unsolved { ref mut cards, cheat: cheat@None } => (cards, cheat),
-                        _ => return None,
-                    };
-                    let goes_on = to.last().copied();
-                    let card = from.pop()?;
-                    // Any card can be placed on empty column (so it wouldn't be cheating
-                    if goes_on == None || goes_on == card.goes_on() { return None; }
-                    *to_cheat = Some(card);
+            Cheat { .. } => {
+                if from_cheat.is_some() { return Err(CheatInTheWay(from)); }
+                let card = *from_cards.last().ok_or(EmptyColumn(from))?;
+                // Placing a card on an empty column or a card it goes on isn't cheating
+                match to_cards.last() {
+                    None => return Err(CheatOntoEmpty(to)),
+                    Some(&top) if card.goes_on() == Some(top) => return Err(CheatOntoMatching { card, onto: top }),
+                    Some(_) => {},
                 }
-                Some(board)
+                from_cards.pop();
+                *to_cheat = Some(card);
             },
-            UnCheat { from, to } => {
-                {
-                    let (from, to) = get_mut_two(&mut board.columns, from, to)?;
-                    let from_cheat = match from {
-                        Column::Unsolved { cheat: cheat@Some(_), .. } => cheat,
-                        _ => return None,
-                    };
-                    let to = match to {
-                        Column::Unsolved { ref mut cards, cheat: None } => cards,
-                        _ => return None,
-                    };
-                    let goes_on = to.last().copied();
-                    let card = from_cheat.take()?;
-                    // Any card can be placed on empty column
-                    if goes_on != None && goes_on != card.goes_on() { return None; }
-                    to.push(card);
+            UnCheat { .. } => {
+                let card = from_cheat.take().ok_or(NoCheat(from))?;
+                if let Some(&top) = to_cards.last() {
+                    if card.goes_on() != Some(top) { return Err(DoesNotGoOn { card, onto: top }); }
                 }
-                Some(board)
+                to_cards.push(card);
             },
-        }?;
+        }
         // Check if any column is solved
         for column in board.columns.iter_mut() {
             if let Column::Unsolved { cards, cheat: None } = column {
-                use Card::*;
-                if &cards[..] == &[T, K, D, V, Ten, Nine, Eight, Seven, Six] {
+                if cards[..] == Column::SOLVED_CARDS {
                     *column = Column::Solved;
                 }
             }
         }
-        Some(board)
+        Ok(board)
+    }
+}
+
+/// Why a [`Move`] can't be made, see [`Move::try_apply`]. Columns are indices into [`Board::columns`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// The board doesn't have this column
+    NoSuchColumn(usize),
+    /// The move is from a column to itself
+    SameColumn(usize),
+    ColumnSolved(usize),
+    /// Nothing can be moved out of the column
+    EmptyColumn(usize),
+    /// The column only has `count` cards
+    NotEnoughCards { column: usize, count: usize },
+    /// The cards to move aren't a run, since `card` doesn't go on `on`
+    NotARun { card: Card, on: Card },
+    /// The card at the start of the cards to move doesn't go on the top card of the target column
+    DoesNotGoOn { card: Card, onto: Card },
+    /// The column has a cheat card on top, so nothing can be moved from under it or placed on it
+    CheatInTheWay(usize),
+    /// Cards can be placed on empty columns without cheating
+    CheatOntoEmpty(usize),
+    /// `card` goes on `onto`, so placing it there isn't cheating
+    CheatOntoMatching { card: Card, onto: Card },
+    /// The column doesn't have a cheat card to move
+    NoCheat(usize),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use MoveError::*;
+        match *self {
+            NoSuchColumn(column) => write!(f, "there is no column {}", column + 1),
+            SameColumn(column) => write!(f, "column {} can't be moved onto itself", column + 1),
+            ColumnSolved(column) => write!(f, "column {} is solved", column + 1),
+            EmptyColumn(column) => write!(f, "column {} is empty", column + 1),
+            NotEnoughCards { column, count } => write!(f, "column {} only has {} cards", column + 1, count),
+            NotARun { card, on } => write!(f, "those cards are not a run: {} doesn't go on {}", card.to_str(), on.to_str()),
+            DoesNotGoOn { card, onto } => write!(f, "{} doesn't go on {}", card.to_str(), onto.to_str()),
+            CheatInTheWay(column) => write!(f, "column {} has a cheat card on top", column + 1),
+            CheatOntoEmpty(column) => write!(f, "column {} is empty, so no cheat is needed", column + 1),
+            CheatOntoMatching { card, onto } => write!(f, "{} goes on {}, so no cheat is needed", card.to_str(), onto.to_str()),
+            NoCheat(column) => write!(f, "column {} has no cheat card", column + 1),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Short notation with columns numbered from 1: `2>5` moves the top card of column 2 onto column 5,
+/// `2>5x3` moves 3 cards, `1c4` cheats the top card of column 1 onto column 4 and `4u2` moves the cheat
+/// card from column 4 onto column 2.
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Move::Normal { from, to, count } if count.get() == 1 => write!(f, "{}>{}", from + 1, to + 1),
+            Move::Normal { from, to, count } => write!(f, "{}>{}x{}", from + 1, to + 1, count),
+            Move::Cheat { from, to } => write!(f, "{}c{}", from + 1, to + 1),
+            Move::UnCheat { from, to } => write!(f, "{}u{}", from + 1, to + 1),
+        }
+    }
+}
+
+/// A [`Move`] that couldn't be parsed, see its [`Display`](std::fmt::Display) impl for the notation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoveError(pub String);
+
+impl std::fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid move {:?}, expected e.g. 2>5, 2>5x3, 1c4 or 4u2", self.0)
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+impl std::str::FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMoveError(s.to_owned());
+        let column = |s: &str| s.parse::<usize>().ok().and_then(|column| column.checked_sub(1)).ok_or_else(error);
+        let s = s.trim();
+        let kind = s.find(['>', 'c', 'u']).ok_or_else(error)?;
+        let (from, rest) = s.split_at(kind);
+        let from = column(from)?;
+        let rest = &rest[1..];
+        Ok(match s.as_bytes()[kind] {
+            b'>' => {
+                let (to, count) = rest.split_once('x').unwrap_or((rest, "1"));
+                let count = count.parse().map_err(|_| error())?;
+                Move::Normal { from, to: column(to)?, count }
+            },
+            b'c' => Move::Cheat { from, to: column(rest)? },
+            _ => Move::UnCheat { from, to: column(rest)? },
+        })
     }
 }
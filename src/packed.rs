@@ -0,0 +1,275 @@
+use crate::cards::*;
+use crate::moves::*;
+
+/// The most columns a [`PackedBoard`] can have
+const MAX_COLUMNS: usize = 8;
+/// The most cards a column of a [`PackedBoard`] can have.
+/// A legal column has at most 14: 5 cards that were dealt, plus a run from K to 6 on top of a T.
+pub(crate) const MAX_CARDS: usize = 16;
+const NIBBLES: usize = 64;
+
+const END: u8 = 0x0;
+const CHEAT: u8 = 0xA;
+const SOLVED: u8 = 0xB;
+
+/// A [`Board`] packed into 256 bits, so that large numbers of boards can be stored during search
+/// without any heap allocations.
+///
+/// The board is stored as a sequence of 4-bit nibbles: the number of columns, then for each column
+/// either `SOLVED`, or its cards (`1..=9`) optionally followed by `CHEAT` and the cheat card, then `END`.
+/// A board with six columns and 36 cards takes at most 49 nibbles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackedBoard {
+    bits: [u128; 2],
+}
+
+impl PackedBoard {
+    /// Returns `None` if the board has more than 8 columns or a column with more than 16 cards,
+    /// neither of which passes [`Board::validate`].
+    pub fn pack(board: &Board) -> Option<Self> {
+        Unpacked::from_board(board)?.pack()
+    }
+    pub fn unpack(&self) -> Board {
+        self.unpacked().to_board()
+    }
+    pub fn is_solved(&self) -> bool {
+        self.unpacked().columns().iter().all(
+            |column| column.solved || (column.len == 0 && column.cheat.is_none())
+        )
+    }
+    /// See [`Board::canonical`]
+    pub fn canonical(&self) -> Self {
+        let mut unpacked = self.unpacked();
+        unpacked.columns_mut().sort_unstable_by(|a, b| a.view().cmp(&b.view()));
+        // Sorting doesn't change the size of the board, so it still fits
+        unpacked.pack().unwrap()
+    }
+    /// See [`Board::possible_moves`]
+    pub fn possible_moves(&self) -> Vec<Move> {
+        let unpacked = self.unpacked();
+        possible_moves(&unpacked.views()[..unpacked.len])
+    }
+    /// See [`Board::score`]
+    pub fn score(&self) -> i64 {
+        let unpacked = self.unpacked();
+        unpacked.views()[..unpacked.len].iter().map(|column| column.score()).sum()
+    }
+    /// See [`Board::lower_bound`]
+    pub fn lower_bound(&self) -> usize {
+        let unpacked = self.unpacked();
+        unpacked.views()[..unpacked.len].iter().map(|column| column.lower_bound()).sum()
+    }
+    fn nibble(&self, i: usize) -> u8 {
+        ((self.bits[i / 32] >> (i % 32 * 4)) & 0xF) as u8
+    }
+    pub(crate) fn unpacked(&self) -> Unpacked {
+        let mut unpacked = Unpacked { len: self.nibble(0).into(), columns: [UnpackedColumn::EMPTY; MAX_COLUMNS] };
+        let mut i = 1;
+        for column in unpacked.columns_mut() {
+            loop {
+                let nibble = self.nibble(i);
+                i += 1;
+                match nibble {
+                    END => break,
+                    SOLVED => column.solved = true,
+                    CHEAT => {
+                        column.cheat = Some(Card::ALL[usize::from(self.nibble(i)) - 1]);
+                        i += 1;
+                    },
+                    card => {
+                        column.cards[column.len] = Card::ALL[usize::from(card) - 1];
+                        column.len += 1;
+                    },
+                }
+            }
+        }
+        unpacked
+    }
+}
+
+impl From<PackedBoard> for Board {
+    fn from(board: PackedBoard) -> Self {
+        board.unpack()
+    }
+}
+
+/// A [`Column`] unpacked onto the stack
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UnpackedColumn {
+    solved: bool,
+    len: usize,
+    cards: [Card; MAX_CARDS],
+    cheat: Option<Card>,
+}
+
+impl UnpackedColumn {
+    const EMPTY: Self = Self { solved: false, len: 0, cards: [Card::Six; MAX_CARDS], cheat: None };
+    fn cards(&self) -> &[Card] {
+        &self.cards[..self.len]
+    }
+    fn view(&self) -> ColumnView<'_> {
+        match self.solved {
+            true => ColumnView::Solved,
+            false => ColumnView::Unsolved { cards: self.cards(), cheat: self.cheat },
+        }
+    }
+}
+
+/// A [`Board`] unpacked onto the stack, for applying moves to a [`PackedBoard`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Unpacked {
+    len: usize,
+    columns: [UnpackedColumn; MAX_COLUMNS],
+}
+
+impl Unpacked {
+    fn from_board(board: &Board) -> Option<Self> {
+        if board.columns.len() > MAX_COLUMNS { return None; }
+        let mut unpacked = Unpacked { len: board.columns.len(), columns: [UnpackedColumn::EMPTY; MAX_COLUMNS] };
+        for (column, unpacked) in board.columns.iter().zip(unpacked.columns_mut()) {
+            match column {
+                Column::Solved => unpacked.solved = true,
+                Column::Unsolved { cards, cheat } => {
+                    if cards.len() > MAX_CARDS { return None; }
+                    unpacked.cards[..cards.len()].copy_from_slice(cards);
+                    unpacked.len = cards.len();
+                    unpacked.cheat = *cheat;
+                },
+            }
+        }
+        Some(unpacked)
+    }
+    fn to_board(self) -> Board {
+        Board {
+            columns: self.columns().iter().map(|column| match column.solved {
+                true => Column::Solved,
+                false => Column::Unsolved { cards: column.cards().to_vec(), cheat: column.cheat },
+            }).collect()
+        }
+    }
+    /// Returns `None` if the board doesn't fit in 256 bits
+    fn pack(&self) -> Option<PackedBoard> {
+        let mut bits = [0u128; 2];
+        let mut i = 0;
+        let mut push = |nibble: u8| -> Option<()> {
+            if i >= NIBBLES { return None; }
+            bits[i / 32] |= u128::from(nibble) << (i % 32 * 4);
+            i += 1;
+            Some(())
+        };
+        push(self.len as u8)?;
+        for column in self.columns() {
+            if column.solved { push(SOLVED)?; }
+            for &card in column.cards() {
+                push(card as u8 + 1)?;
+            }
+            if let Some(cheat) = column.cheat {
+                push(CHEAT)?;
+                push(cheat as u8 + 1)?;
+            }
+            push(END)?;
+        }
+        Some(PackedBoard { bits })
+    }
+    fn columns(&self) -> &[UnpackedColumn] {
+        &self.columns[..self.len]
+    }
+    fn columns_mut(&mut self) -> &mut [UnpackedColumn] {
+        &mut self.columns[..self.len]
+    }
+    fn views(&self) -> [ColumnView<'_>; MAX_COLUMNS] {
+        let mut views = [ColumnView::Solved; MAX_COLUMNS];
+        for (view, column) in views.iter_mut().zip(self.columns()) {
+            *view = column.view();
+        }
+        views
+    }
+    /// See [`Move::apply`]
+    pub(crate) fn apply(mut self, move_: &Move) -> Option<PackedBoard> {
+        use Move::*;
+        let (from, to) = match *move_ {
+            Normal { from, to, .. } | Cheat { from, to } | UnCheat { from, to } => (from, to),
+        };
+        if from == to || from >= self.len || to >= self.len { return None; }
+        let (mut from_col, mut to_col) = (self.columns[from], self.columns[to]);
+        if from_col.solved || to_col.solved || to_col.cheat.is_some() { return None; }
+        let goes_on = to_col.cards().last().copied();
+        match *move_ {
+            Normal { count, .. } => {
+                if from_col.cheat.is_some() { return None; }
+                let start = from_col.len.checked_sub(count.get())?;
+                let to_be_moved = &from_col.cards()[start..];
+                for pair in to_be_moved.windows(2) {
+                    if pair[1].goes_on() != Some(pair[0]) { return None; }
+                }
+                // Any card can be placed on empty column
+                if goes_on.is_some() && goes_on != to_be_moved[0].goes_on() { return None; }
+                if to_col.len + to_be_moved.len() > MAX_CARDS { return None; }
+                to_col.cards[to_col.len..][..to_be_moved.len()].copy_from_slice(to_be_moved);
+                to_col.len += to_be_moved.len();
+                from_col.len = start;
+            },
+            Cheat { .. } => {
+                if from_col.cheat.is_some() { return None; }
+                let card = *from_col.cards().last()?;
+                // Any card can be placed on empty column (so it wouldn't be cheating
+                if goes_on.is_none() || goes_on == card.goes_on() { return None; }
+                from_col.len -= 1;
+                to_col.cheat = Some(card);
+            },
+            UnCheat { .. } => {
+                let card = from_col.cheat.take()?;
+                // Any card can be placed on empty column
+                if goes_on.is_some() && goes_on != card.goes_on() { return None; }
+                if to_col.len >= MAX_CARDS { return None; }
+                to_col.cards[to_col.len] = card;
+                to_col.len += 1;
+            },
+        }
+        self.columns[from] = from_col;
+        self.columns[to] = to_col;
+        // Check if any column is solved
+        for column in self.columns_mut() {
+            if !column.solved && column.cheat.is_none() && column.cards() == Column::SOLVED_CARDS {
+                *column = UnpackedColumn { solved: true, ..UnpackedColumn::EMPTY };
+            }
+        }
+        self.pack()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::tests::{Rng, random_walk, solvable_deals};
+
+    #[test]
+    fn packed_matches_board() {
+        let mut rng = Rng(0xFEDC_BA98_7654_3210);
+        for _ in 0..200 {
+            for board in random_walk(&mut rng, 100) {
+                let packed = PackedBoard::pack(&board).unwrap();
+                assert_eq!(packed.unpack(), board);
+                assert_eq!(packed.is_solved(), board.is_solved());
+                assert_eq!(packed.score(), board.score());
+                assert_eq!(packed.lower_bound(), board.lower_bound());
+                let moves = board.possible_moves();
+                assert_eq!(packed.possible_moves(), moves);
+                for move_ in Move::all_moves() {
+                    assert_eq!(move_.apply_packed(&packed).map(Board::from), move_.apply(&board), "{:?} {:?}", move_, board);
+                }
+            }
+        }
+        // Random walks rarely solve columns, so also check along a solution
+        for mut board in solvable_deals() {
+            let mut packed = PackedBoard::pack(&board).unwrap();
+            let (_, moves) = board.clone().solve().unwrap();
+            for move_ in moves {
+                board = move_.apply(&board).unwrap();
+                packed = move_.apply_packed(&packed).unwrap();
+                assert_eq!(packed.unpack(), board);
+            }
+            assert!(packed.is_solved());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::cards::*;
+use crate::moves::*;
+
+/// A game: the board it started from and the moves made from it, so that solutions can be saved, shared
+/// and replayed.
+///
+/// As text, a record is the board in the notation of its [`Display`](std::fmt::Display) impl, a blank line,
+/// and then the moves in the notation of [`Move`], one per line and numbered from 1:
+///
+/// ```text
+/// 87KVK6
+/// 6XVX78
+/// 6V99KD
+/// 69XVDT
+/// D8KTTD
+/// 79XT78
+///
+/// 1. 3>4x2
+/// 2. 2>3
+/// 3. 1>2
+/// ```
+///
+/// When parsing, blank lines are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub board: Board,
+    pub moves: Vec<Move>,
+}
+
+impl GameRecord {
+    pub fn new(board: Board, moves: Vec<Move>) -> Self {
+        Self { board, moves }
+    }
+    /// Makes the moves in turn, returning the board after each, or the first move that can't be made.
+    /// The boards don't need to end up solved.
+    pub fn boards(&self) -> Result<Vec<Board>, SolutionError> {
+        let mut boards: Vec<Board> = Vec::with_capacity(self.moves.len());
+        for (index, move_) in self.moves.iter().enumerate() {
+            let board = boards.last().unwrap_or(&self.board);
+            let next = move_.try_apply(board).map_err(|error| SolutionError::IllegalMove { index, move_: *move_, error })?;
+            boards.push(next);
+        }
+        Ok(boards)
+    }
+    /// Checks that the moves solve the board, see [`Board::verify_solution`]
+    pub fn verify(&self) -> Result<Board, SolutionError> {
+        self.board.verify_solution(&self.moves)
+    }
+}
+
+impl std::fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.board)?;
+        if !self.moves.is_empty() {
+            writeln!(f)?;
+        }
+        for (i, move_) in self.moves.iter().enumerate() {
+            write!(f, "\n{}. {}", i + 1, move_)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`GameRecord`] couldn't be parsed. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRecordError {
+    Board(ParseBoardError),
+    /// The line should have been move number `expected`, written as e.g. `1. 2>5`
+    MoveNumber { line: usize, expected: usize },
+    Move { line: usize, error: ParseMoveError },
+}
+
+impl std::fmt::Display for ParseRecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseRecordError::Board(error) => write!(f, "{}", error),
+            ParseRecordError::MoveNumber { line, expected } => {
+                write!(f, "line {}: expected move {}, written as e.g. \"{}. 2>5\"", line, expected, expected)
+            },
+            ParseRecordError::Move { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for ParseRecordError {}
+
+impl From<ParseBoardError> for ParseRecordError {
+    fn from(error: ParseBoardError) -> Self {
+        ParseRecordError::Board(error)
+    }
+}
+
+impl std::str::FromStr for GameRecord {
+    type Err = ParseRecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Board lines never contain a `.`, so the moves start at the first line that does
+        let lines: Vec<&str> = s.lines().collect();
+        let first_move = lines.iter().position(|line| line.contains('.')).unwrap_or(lines.len());
+        let board = lines[..first_move].join("\n").parse()?;
+        let mut moves = vec![];
+        for (i, line) in lines.iter().enumerate().skip(first_move) {
+            let line_number = i + 1;
+            if line.trim().is_empty() { continue; }
+            let expected = moves.len() + 1;
+            let move_ = match line.split_once('.') {
+                Some((number, move_)) if number.trim().parse() == Ok(expected) => move_,
+                _ => return Err(ParseRecordError::MoveNumber { line: line_number, expected }),
+            };
+            moves.push(move_.trim().parse().map_err(|error| ParseRecordError::Move { line: line_number, error })?);
+        }
+        Ok(Self { board, moves })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::tests::solvable_deals;
+
+    #[test]
+    fn records_round_trip() {
+        for board in solvable_deals() {
+            let (solved, moves) = board.clone().solve().unwrap();
+            let record = GameRecord::new(board, moves);
+            assert_eq!(record.to_string().parse::<GameRecord>(), Ok(record.clone()));
+            assert_eq!(record.verify(), Ok(solved.clone()));
+            let boards = record.boards().unwrap();
+            assert_eq!(boards.len(), record.moves.len());
+            assert_eq!(boards.last(), Some(&solved));
+        }
+        let record = GameRecord::new(Board::deal(1), vec![]);
+        assert_eq!(record.to_string().parse::<GameRecord>(), Ok(record));
+    }
+
+    #[test]
+    fn bad_records_are_rejected() {
+        let board = Board::deal(1).to_string();
+        let record = |moves: &str| format!("{}\n\n{}", board, moves).parse::<GameRecord>();
+        assert_eq!(record("1. 1>2\n\n2. 2c3").map(|record| record.moves.len()), Ok(2));
+        assert_eq!(record("1. 1>2\n3. 2c3"), Err(ParseRecordError::MoveNumber { line: 9, expected: 2 }));
+        assert_eq!(record("1. 1>2\n2>3"), Err(ParseRecordError::MoveNumber { line: 9, expected: 2 }));
+        assert_eq!(
+            record("1. 1>2\n2. 2?3"),
+            Err(ParseRecordError::Move { line: 9, error: ParseMoveError("2?3".to_owned()) }),
+        );
+        assert!(matches!("6Q\n\n1. 1>2".parse::<GameRecord>(), Err(ParseRecordError::Board(_))));
+        // Parsing doesn't check that the moves can be made
+        let record = record("1. 1>1").unwrap();
+        assert!(matches!(record.boards(), Err(SolutionError::IllegalMove { index: 0, error: MoveError::SameColumn(_), .. })));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::cards::*;
+use crate::moves::*;
+use crate::solver::*;
+use serde::{Deserialize, Serialize};
+
+/// The version of the [`SolveReport`] schema. Adding fields doesn't change it, but changing or removing them does.
+pub const REPORT_VERSION: u32 = 1;
+
+/// How a solve ended, see [`SolveOutcome`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportResult {
+    Solved,
+    Unsolvable,
+    GaveUp,
+    Invalid,
+}
+
+/// The result of solving a board, for other programs to read, e.g. as JSON:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "board": { "columns": [{ "type": "unsolved", "cards": ["8", "7", "K", "V", "K", "6"], "cheat": null }, ...] },
+///   "result": "solved",
+///   "moves": [{ "type": "normal", "from": 4, "to": 3, "count": 1 }, { "type": "cheat", "from": 2, "to": 5 }, ...],
+///   "cheats": 1,
+///   "optimal": false,
+///   "stats": { "expanded": 1234, ..., "elapsed_secs": 0.005 }
+/// }
+/// ```
+///
+/// Cards are written as their glyphs from [`Card::to_str`], and columns are indices into [`Board::columns`],
+/// counting from 0. Fields that don't apply to the result are left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolveReport {
+    /// [`REPORT_VERSION`]
+    pub version: u32,
+    /// The board that was solved
+    pub board: Board,
+    pub result: ReportResult,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moves: Option<Vec<Move>>,
+    /// The board after each of `moves`, if they were asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boards: Option<Vec<Board>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cheats: Option<usize>,
+    /// Whether no solution with a lower cost exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimal: Option<bool>,
+    /// The limit that was reached, if the solver gave up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<Limit>,
+    /// Why the board isn't a legal position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub stats: SolveStats,
+}
+
+impl SolveReport {
+    /// Reports `outcome`, the result of solving `board`, with the board after each move if `with_boards`
+    pub fn new<C>(board: Board, outcome: SolveOutcome<C>, with_boards: bool) -> Self {
+        let mut report = Self {
+            version: REPORT_VERSION,
+            board,
+            result: ReportResult::Solved,
+            moves: None,
+            boards: None,
+            cheats: None,
+            optimal: None,
+            limit: None,
+            error: None,
+            stats: outcome.stats().clone(),
+        };
+        match outcome {
+            SolveOutcome::Solved { moves, optimal, .. } => {
+                if with_boards {
+                    let mut board = report.board.clone();
+                    report.boards = Some(moves.iter().map(|move_| {
+                        board = move_.apply(&board).unwrap();
+                        board.clone()
+                    }).collect());
+                }
+                report.cheats = Some(moves.iter().filter(|move_| move_.is_cheat()).count());
+                report.optimal = Some(optimal);
+                report.moves = Some(moves);
+            },
+            SolveOutcome::Unsolvable { .. } => report.result = ReportResult::Unsolvable,
+            SolveOutcome::GaveUp { limit, .. } => {
+                report.result = ReportResult::GaveUp;
+                report.limit = Some(limit);
+            },
+            SolveOutcome::Invalid(error) => {
+                report.result = ReportResult::Invalid;
+                report.error = Some(error.to_string());
+            },
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristics::*;
+
+    #[test]
+    fn reports_round_trip() {
+        let board = Board::deal(4);
+        let outcome = board.clone().solve_with(Score, MoveCount, &SolveOptions::default());
+        let report = SolveReport::new(board.clone(), outcome, true);
+        assert_eq!(report.result, ReportResult::Solved);
+        let moves = report.moves.as_ref().unwrap();
+        assert_eq!(report.boards.as_ref().unwrap().last(), board.verify_solution(moves).ok().as_ref());
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<SolveReport>(&json).unwrap(), report);
+
+        let options = SolveOptions { max_nodes: Some(10), ..SolveOptions::default() };
+        let report = SolveReport::new(board.clone(), board.solve_optimal_with(LowerBound, MoveCount, &options), false);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["result"], "gave_up");
+        assert_eq!(json["limit"], "nodes");
+        assert!(json.get("moves").is_none());
+        assert!(json["stats"]["elapsed_secs"].is_f64());
+        assert_eq!(serde_json::from_value::<SolveReport>(json).unwrap(), report);
+    }
+
+    #[test]
+    fn schema_is_stable() {
+        use Card::*;
+        let column = Column::Unsolved { cards: vec![Six, Ten, T], cheat: Some(K) };
+        assert_eq!(
+            serde_json::to_string(&column).unwrap(),
+            r#"{"type":"unsolved","cards":["6","X","T"],"cheat":"K"}"#,
+        );
+        assert_eq!(serde_json::to_string(&Column::Solved).unwrap(), r#"{"type":"solved"}"#);
+        for (move_, json) in [
+            ("2>5x3", r#"{"type":"normal","from":1,"to":4,"count":3}"#),
+            ("1c4", r#"{"type":"cheat","from":0,"to":3}"#),
+            ("4u2", r#"{"type":"un_cheat","from":3,"to":1}"#),
+        ] {
+            let move_: Move = move_.parse().unwrap();
+            assert_eq!(serde_json::to_string(&move_).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Move>(json).unwrap(), move_);
+        }
+    }
+}
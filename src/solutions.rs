@@ -0,0 +1,149 @@
+use crate::cards::*;
+use crate::heuristics::*;
+use crate::moves::*;
+use crate::packed::*;
+use crate::solver::*;
+use std::collections::{HashMap, HashSet};
+
+/// Every shortest solution of a board, as an iterator over their moves.
+///
+/// Since most moves can be undone, a board that has any solution has infinitely many, so only the shortest
+/// are considered. Two solutions are distinct if their sequences of moves are, even if they pass through the
+/// same boards with the columns in a different order.
+///
+/// Building this finds every board that is on some shortest solution, which forms a directed acyclic graph.
+/// [`MinimalSolutions::total`] counts the paths through it without listing them, and the iterator lists
+/// them lazily, so use [`Iterator::take`] to limit how many are listed.
+#[derive(Debug, Clone)]
+pub struct MinimalSolutions {
+    /// For each depth, the number of shortest solutions from each board at that depth which is on one,
+    /// by canonical form. Boards that are on no shortest solution are left out.
+    ways: Vec<HashMap<PackedBoard, u128>>,
+    /// The boards on the current path, and the moves from each that haven't been tried yet
+    stack: Vec<(PackedBoard, Vec<Move>)>,
+    /// The moves to the last board in `stack`
+    path: Vec<Move>,
+}
+
+impl MinimalSolutions {
+    /// Finds every board on a shortest solution of `board`.
+    ///
+    /// Fails if `board` has no solution, is not a legal position, or a limit was reached.
+    pub fn new(board: &Board, options: &SolveOptions) -> Result<Self, SolveError> {
+        let (moves, _, _) = board.clone().solve_optimal_with(LowerBound, MoveCount, options).into_result()?;
+        let len = moves.len();
+        let limits = Limits::new(options);
+        let mut expanded = 0;
+        let start = board.packed_start().map_err(SolveError::Invalid)?;
+        // Breadth-first search, skipping boards that can't be solved in the remaining number of moves
+        let mut seen: HashSet<PackedBoard> = HashSet::from([start.canonical()]);
+        let mut layers: Vec<HashMap<PackedBoard, PackedBoard>> = vec![HashMap::from([(start.canonical(), start)])];
+        for depth in 0..len {
+            let mut next = HashMap::new();
+            for board in layers[depth].values() {
+                // Every seen board but the start is also in a layer, as a key and value and a control byte
+                let memory = memory(seen.len(), seen.len() * (std::mem::size_of::<(PackedBoard, PackedBoard)>() + 1));
+                if let Some(limit) = limits.reached(expanded, memory) { return Err(SolveError::GaveUp(limit)); }
+                expanded += 1;
+                limits.progress(board, expanded, next.len(), depth);
+                for move_ in board.possible_moves() {
+                    let Some(child) = move_.apply_packed(board) else { continue; };
+                    if depth + 1 + child.lower_bound() > len { continue; }
+                    if seen.insert(child.canonical()) {
+                        next.insert(child.canonical(), child);
+                    }
+                }
+            }
+            layers.push(next);
+        }
+        // Count the solutions backwards from the solved boards
+        let mut ways = vec![HashMap::new(); len + 1];
+        ways[len] = layers[len].iter().filter(|(_, board)| board.is_solved()).map(|(&key, _)| (key, 1)).collect();
+        for depth in (0..len).rev() {
+            for (&key, board) in &layers[depth] {
+                let total = board.possible_moves().iter()
+                    .filter_map(|move_| move_.apply_packed(board))
+                    .filter_map(|child| ways[depth + 1].get(&child.canonical()))
+                    .fold(0u128, |total, &ways| total.saturating_add(ways));
+                if total > 0 {
+                    ways[depth].insert(key, total);
+                }
+            }
+        }
+        let mut solutions = Self { ways, stack: vec![], path: vec![] };
+        let moves = solutions.next_moves(&start, 0);
+        solutions.stack.push((start, moves));
+        Ok(solutions)
+    }
+    /// The number of moves in each solution
+    pub fn solution_len(&self) -> usize {
+        self.ways.len() - 1
+    }
+    /// The total number of shortest solutions (saturating at [`u128::MAX`]), including any already listed
+    pub fn total(&self) -> u128 {
+        self.ways[0].values().copied().sum()
+    }
+    /// The moves from `board`, at `depth`, to a board on a shortest solution
+    fn next_moves(&self, board: &PackedBoard, depth: usize) -> Vec<Move> {
+        board.possible_moves().into_iter()
+            .filter(|move_| move_.apply_packed(board).is_some_and(|child| self.ways[depth + 1].contains_key(&child.canonical())))
+            .collect()
+    }
+}
+
+impl Iterator for MinimalSolutions {
+    type Item = Vec<Move>;
+
+    fn next(&mut self) -> Option<Vec<Move>> {
+        loop {
+            let depth = self.path.len();
+            // Every board on the path is on a shortest solution, so this one is solved
+            if depth == self.solution_len() && !self.stack.is_empty() {
+                let solution = self.path.clone();
+                self.stack.pop();
+                self.path.pop();
+                return Some(solution);
+            }
+            let (board, moves) = self.stack.last_mut()?;
+            let Some(move_) = moves.pop() else {
+                self.stack.pop();
+                self.path.pop();
+                continue;
+            };
+            let child = move_.apply_packed(board).unwrap();
+            let moves = self.next_moves(&child, depth + 1);
+            self.stack.push((child, moves));
+            self.path.push(move_);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::tests::{play, solvable_deals};
+
+    #[test]
+    fn solutions_are_counted_and_listed() {
+        for board in solvable_deals() {
+            let (_, optimal) = board.clone().solve_optimal().unwrap();
+            // Start near the end of the solution, so that every solution can be listed
+            let board = play(board, &optimal[..optimal.len() - 10]);
+            let solutions = MinimalSolutions::new(&board, &SolveOptions::default()).unwrap();
+            assert_eq!(solutions.solution_len(), 10);
+            let total = solutions.total();
+            let listed: Vec<Vec<Move>> = solutions.collect();
+            assert_eq!(listed.len() as u128, total);
+            assert!(listed.contains(&optimal[optimal.len() - 10..].to_vec()));
+            for moves in &listed {
+                assert_eq!(moves.len(), 10);
+                assert!(board.verify_solution(moves).is_ok());
+            }
+            let distinct: std::collections::BTreeSet<&Vec<Move>> = listed.iter().collect();
+            assert_eq!(distinct.len(), listed.len());
+        }
+        let empty = Column::Unsolved { cards: vec![], cheat: None };
+        let solved = Board { columns: vec![Column::Solved, Column::Solved, Column::Solved, Column::Solved, empty.clone(), empty] };
+        assert_eq!(MinimalSolutions::new(&solved, &SolveOptions::default()).unwrap().collect::<Vec<_>>(), vec![vec![]]);
+    }
+}
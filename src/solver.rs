@@ -0,0 +1,338 @@
+use crate::cards::*;
+use crate::moves::*;
+use crate::packed::*;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicI64, Ordering}};
+use std::time::{Duration, Instant};
+
+/// Limits on how much work a solver may do before giving up, and how to report its progress.
+///
+/// The default has no limits and doesn't report progress.
+#[derive(Debug, Clone, Default)]
+pub struct SolveOptions {
+    /// Give up after this much wall-clock time
+    pub time_limit: Option<Duration>,
+    /// Give up after expanding this many boards
+    pub max_nodes: Option<usize>,
+    /// Give up once the search's seen set, queue and move tree take approximately this many bytes, allowing for
+    /// the spare capacity of hash tables and vectors
+    pub max_memory: Option<usize>,
+    /// Give up once this is cancelled
+    pub cancel: Option<CancelToken>,
+    /// Called with the progress of the search, at most once per `progress_interval`
+    pub progress: Option<ProgressCallback>,
+    pub progress_interval: Duration,
+}
+
+/// Cancels a search from another thread. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A snapshot of a search in progress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Boards whose children were generated
+    pub expanded: usize,
+    /// Boards waiting to be expanded
+    pub queue_len: usize,
+    /// The lowest [`Board::score`] of any board expanded so far
+    pub best_score: i64,
+    /// The number of moves to the board being expanded
+    pub depth: usize,
+    pub elapsed: Duration,
+}
+
+/// See [`SolveOptions::progress`]
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback(..)")
+    }
+}
+
+/// Which of the [`SolveOptions`] limits was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Limit {
+    Time,
+    Nodes,
+    Memory,
+    /// [`SolveOptions::cancel`] was cancelled
+    Cancelled,
+}
+
+/// How much work a solver did.
+///
+/// For [`Board::solve_with_threads`], `peak_queue` and `peak_memory` are the sums of each thread's peak,
+/// so they may overestimate the true peak.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolveStats {
+    /// Boards whose children were generated
+    pub expanded: usize,
+    /// Boards reached by a legal move from an expanded board
+    pub generated: usize,
+    /// Generated boards rejected because an equivalent board had already been seen
+    pub duplicates: usize,
+    /// Boards skipped when taken from the queue because an equivalent board was expanded after they were queued.
+    /// These are not counted in `duplicates`.
+    pub stale: usize,
+    /// Distinct boards in the seen set
+    pub seen: usize,
+    /// The most boards waiting in the queue at once
+    pub peak_queue: usize,
+    /// The approximate peak number of bytes used by the seen set, the queue and the move tree
+    pub peak_memory: usize,
+    /// The most moves to any expanded board
+    pub max_depth: usize,
+    /// With the `serde` feature, serialized as `elapsed_secs`, a number of seconds
+    #[cfg_attr(feature = "serde", serde(rename = "elapsed_secs", with = "secs"))]
+    pub elapsed: Duration,
+}
+
+/// Serializes a [`Duration`] as a number of seconds
+#[cfg(feature = "serde")]
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+impl SolveStats {
+    /// The average number of boards generated per expanded board
+    pub fn branching_factor(&self) -> f64 {
+        if self.expanded == 0 { return 0.0; }
+        self.generated as f64 / self.expanded as f64
+    }
+    /// Records expanding a board `depth` moves from the start, with `queue_len` boards in the queue and `memory`
+    /// bytes in use, as estimated by [`search_memory`]
+    pub(crate) fn expand(&mut self, depth: usize, queue_len: usize, memory: usize) {
+        self.expanded += 1;
+        self.max_depth = self.max_depth.max(depth);
+        self.peak_queue = self.peak_queue.max(queue_len);
+        self.peak_memory = self.peak_memory.max(memory);
+    }
+    /// Adds the stats of another thread of the same search
+    #[cfg(feature = "thread")]
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.expanded += other.expanded;
+        self.generated += other.generated;
+        self.duplicates += other.duplicates;
+        self.stale += other.stale;
+        self.seen += other.seen;
+        self.peak_queue += other.peak_queue;
+        self.peak_memory += other.peak_memory;
+        self.max_depth = self.max_depth.max(other.max_depth);
+    }
+}
+
+impl std::fmt::Display for SolveStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Expanded:         {}", self.expanded)?;
+        writeln!(f, "Generated:        {}", self.generated)?;
+        writeln!(f, "Duplicates:       {}", self.duplicates)?;
+        writeln!(f, "Stale:            {}", self.stale)?;
+        writeln!(f, "Distinct:         {}", self.seen)?;
+        writeln!(f, "Peak queue:       {}", self.peak_queue)?;
+        writeln!(f, "Peak memory:      {:.1} MiB", self.peak_memory as f64 / (1 << 20) as f64)?;
+        writeln!(f, "Max depth:        {}", self.max_depth)?;
+        writeln!(f, "Branching factor: {:.2}", self.branching_factor())?;
+        write!(f, "Time:             {:.3?}", self.elapsed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveOutcome<C> {
+    Solved {
+        board: Board,
+        moves: Vec<Move>,
+        /// The total cost of `moves`
+        cost: C,
+        /// `true` if no solution with a lower cost exists. Always set by [`Board::solve_optimal_with`], and by
+        /// the other solvers only for a board that is already solved.
+        optimal: bool,
+        stats: SolveStats,
+    },
+    /// The whole reachable search space was searched without finding a solution
+    Unsolvable {
+        stats: SolveStats,
+    },
+    /// A limit was reached before finding a solution
+    GaveUp {
+        limit: Limit,
+        stats: SolveStats,
+    },
+    /// The board is not a legal position, so wasn't searched
+    Invalid(InvalidBoard),
+}
+
+impl<C> SolveOutcome<C> {
+    /// The solved board and the moves to get there, if solved
+    pub fn solution(self) -> Option<(Board, Vec<Move>)> {
+        match self {
+            SolveOutcome::Solved { board, moves, .. } => Some((board, moves)),
+            _ => None,
+        }
+    }
+    /// The moves, their cost and the stats if solved, or why not
+    pub fn into_result(self) -> Result<(Vec<Move>, C, SolveStats), SolveError> {
+        match self {
+            SolveOutcome::Solved { moves, cost, stats, .. } => Ok((moves, cost, stats)),
+            SolveOutcome::Unsolvable { .. } => Err(SolveError::Unsolvable),
+            SolveOutcome::GaveUp { limit, .. } => Err(SolveError::GaveUp(limit)),
+            SolveOutcome::Invalid(error) => Err(SolveError::Invalid(error)),
+        }
+    }
+    pub fn stats(&self) -> &SolveStats {
+        const NOT_SEARCHED: &SolveStats = &SolveStats {
+            expanded: 0,
+            generated: 0,
+            duplicates: 0,
+            stale: 0,
+            seen: 0,
+            peak_queue: 0,
+            peak_memory: 0,
+            max_depth: 0,
+            elapsed: Duration::ZERO,
+        };
+        match self {
+            SolveOutcome::Solved { stats, .. } | SolveOutcome::Unsolvable { stats } | SolveOutcome::GaveUp { stats, .. } => stats,
+            SolveOutcome::Invalid(_) => NOT_SEARCHED,
+        }
+    }
+}
+
+/// Why a search didn't find a solution, for results built from one or more [`SolveOutcome`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The board has no solution
+    Unsolvable,
+    /// A limit was reached before finding a solution
+    GaveUp(Limit),
+    /// The board is not a legal position, so wasn't searched
+    Invalid(InvalidBoard),
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::Unsolvable => f.write_str("could not solve"),
+            SolveError::GaveUp(limit) => write!(f, "gave up ({:?} limit reached)", limit),
+            SolveError::Invalid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Bytes used by each board in a seen set: the packed board and the hash table's control byte
+const SEEN_ENTRY_BYTES: usize = std::mem::size_of::<PackedBoard>() + 1;
+
+/// Hash tables double their capacity when 7/8 full and vectors when full, so just after growing they use only
+/// about half of what they have allocated. Memory estimates assume this worst case.
+const CAPACITY_OVERHEAD: usize = 2;
+
+/// Estimates the bytes used by a search with `seen` boards in its seen set and `other` bytes of other data
+pub(crate) fn memory(seen: usize, other: usize) -> usize {
+    CAPACITY_OVERHEAD * (seen * SEEN_ENTRY_BYTES + other)
+}
+
+/// Estimates the bytes used by a best-first search with `seen` boards in its seen set, `nodes` in its move tree,
+/// and `queued` `Item`s in its queue
+pub(crate) fn search_memory<Item>(seen: usize, nodes: usize, queued: usize) -> usize {
+    memory(seen, nodes * std::mem::size_of::<Option<(usize, Move)>>() + queued * std::mem::size_of::<Item>())
+}
+
+/// How many boards to expand between checking whether to report progress
+const PROGRESS_CHECK_INTERVAL: usize = 256;
+
+/// Checks a search against the [`SolveOptions`] limits, and reports its progress
+#[derive(Debug)]
+pub(crate) struct Limits {
+    start: Instant,
+    deadline: Option<Instant>,
+    max_nodes: Option<usize>,
+    max_memory: Option<usize>,
+    cancel: Option<CancelToken>,
+    progress: Option<(ProgressCallback, Duration)>,
+    last_progress: Mutex<Instant>,
+    best_score: AtomicI64,
+}
+
+impl Limits {
+    pub(crate) fn new(options: &SolveOptions) -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            deadline: options.time_limit.and_then(|limit| start.checked_add(limit)),
+            max_nodes: options.max_nodes,
+            max_memory: options.max_memory,
+            cancel: options.cancel.clone(),
+            progress: options.progress.clone().map(|progress| (progress, options.progress_interval)),
+            last_progress: Mutex::new(start),
+            best_score: AtomicI64::new(i64::MAX),
+        }
+    }
+    /// The limit that has been reached after expanding `expanded` boards with an estimated `memory` bytes in use,
+    /// if any
+    pub(crate) fn reached(&self, expanded: usize, memory: usize) -> Option<Limit> {
+        if self.max_nodes.is_some_and(|max| expanded >= max) { return Some(Limit::Nodes); }
+        if self.max_memory.is_some_and(|max| memory >= max) { return Some(Limit::Memory); }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) { return Some(Limit::Time); }
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) { return Some(Limit::Cancelled); }
+        None
+    }
+    /// Called for each expanded `board`. Calls the progress callback if it is time to.
+    pub(crate) fn progress(&self, board: &PackedBoard, expanded: usize, queue_len: usize, depth: usize) {
+        let Some((callback, interval)) = &self.progress else { return; };
+        let best_score = self.best_score.fetch_min(board.score(), Ordering::Relaxed).min(board.score());
+        if !expanded.is_multiple_of(PROGRESS_CHECK_INTERVAL) { return; }
+        // Another thread is already reporting
+        let Ok(mut last_progress) = self.last_progress.try_lock() else { return; };
+        let now = Instant::now();
+        if now.duration_since(*last_progress) < *interval { return; }
+        *last_progress = now;
+        callback.0(&Progress {
+            expanded,
+            queue_len,
+            best_score,
+            depth,
+            elapsed: now.duration_since(self.start),
+        });
+    }
+    /// Fills in the time taken so far
+    pub(crate) fn finish(&self, stats: SolveStats) -> SolveStats {
+        SolveStats { elapsed: self.start.elapsed(), ..stats }
+    }
+    pub(crate) fn give_up<C>(&self, limit: Limit, stats: SolveStats) -> SolveOutcome<C> {
+        SolveOutcome::GaveUp { limit, stats: self.finish(stats) }
+    }
+}
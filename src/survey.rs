@@ -0,0 +1,199 @@
+use crate::analysis::*;
+use crate::cards::*;
+use crate::heuristics::*;
+use crate::solver::*;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The result of solving one numbered deal, see [`Board::deal`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DealResult {
+    pub deal: u64,
+    /// Includes the length of the shortest solution
+    pub solvability: Solvability,
+    /// The fewest cheats in any solution, if the search for it finished
+    pub cheats: Option<usize>,
+    /// The number of boards expanded while searching for the shortest solution, as a measure of difficulty
+    pub expanded: usize,
+    /// The time taken by both searches
+    pub elapsed: Duration,
+}
+
+impl DealResult {
+    /// Solves a deal with [`Board::solve_optimal_with`] twice: once for the fewest moves and once for the fewest cheats.
+    /// The limits in `options` apply to each search separately.
+    pub fn new(deal: u64, options: &SolveOptions) -> Self {
+        let board = Board::deal(deal);
+        let outcome = board.clone().solve_optimal_with(LowerBound, MoveCount, options);
+        let expanded = outcome.stats().expanded;
+        let mut elapsed = outcome.stats().elapsed;
+        let solvability = Solvability::new(outcome);
+        let cheats = match solvability {
+            Solvability::Solvable { .. } => {
+                let outcome = board.solve_optimal_with(CheatLowerBound, CheatsThenMoves, options);
+                elapsed += outcome.stats().elapsed;
+                match outcome {
+                    SolveOutcome::Solved { cost, .. } => Some(cost.cheats),
+                    _ => None,
+                }
+            },
+            Solvability::Unsolvable | Solvability::Unknown(_) | Solvability::Invalid(_) => None,
+        };
+        Self { deal, solvability, cheats, expanded, elapsed }
+    }
+}
+
+/// Solves every deal in `deals` with [`DealResult::new`], returning the results in the same order.
+///
+/// With the `thread` feature, deals are solved in parallel, one per CPU.
+pub fn survey(deals: &[u64], options: &SolveOptions) -> Vec<DealResult> {
+    #[cfg(feature = "thread")]
+    let threads = num_cpus::get();
+    #[cfg(not(feature = "thread"))]
+    let threads = 1;
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<DealResult>>> = Mutex::new(vec![None; deals.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(&deal) = deals.get(index) else { break; };
+                    let result = DealResult::new(deal, options);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+/// Writes one line of CSV per deal, with a header:
+/// the deal number, the result (`solvable`, `unsolvable`, `invalid` or the limit reached), the number of moves and
+/// cheats (empty if unknown), the number of boards expanded and the time in seconds.
+pub fn write_csv(results: &[DealResult], mut out: impl std::io::Write) -> std::io::Result<()> {
+    writeln!(out, "deal,result,moves,cheats,expanded,seconds")?;
+    for result in results {
+        let (status, moves) = match &result.solvability {
+            Solvability::Solvable { remaining } => ("solvable".to_owned(), remaining.to_string()),
+            Solvability::Unsolvable => ("unsolvable".to_owned(), String::new()),
+            Solvability::Unknown(limit) => (format!("{:?}", limit).to_lowercase(), String::new()),
+            Solvability::Invalid(_) => ("invalid".to_owned(), String::new()),
+        };
+        let cheats = result.cheats.map_or(String::new(), |cheats| cheats.to_string());
+        writeln!(
+            out, "{},{},{},{},{},{:.3}",
+            result.deal, status, moves, cheats, result.expanded, result.elapsed.as_secs_f64(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Totals over the results of a [`survey`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub deals: usize,
+    pub solvable: usize,
+    pub unsolvable: usize,
+    /// Deals where a limit was reached before finding a solution
+    pub unknown: usize,
+    /// Deals that are not legal positions. [`Board::deal`] only makes legal positions, so this is a bug.
+    pub invalid: usize,
+    /// The number of solvable deals by the length of their shortest solution
+    pub moves: BTreeMap<usize, usize>,
+    /// The number of solvable deals by the fewest cheats needed
+    pub cheats: BTreeMap<usize, usize>,
+    /// The solvable deals which took the most boards to solve, hardest first
+    pub hardest: Vec<u64>,
+}
+
+impl Summary {
+    /// How many of the hardest deals to keep
+    const HARDEST: usize = 10;
+
+    pub fn new(results: &[DealResult]) -> Self {
+        let mut summary = Summary { deals: results.len(), ..Summary::default() };
+        for result in results {
+            match result.solvability {
+                Solvability::Solvable { remaining } => {
+                    summary.solvable += 1;
+                    *summary.moves.entry(remaining).or_default() += 1;
+                },
+                Solvability::Unsolvable => summary.unsolvable += 1,
+                Solvability::Unknown(_) => summary.unknown += 1,
+                Solvability::Invalid(_) => summary.invalid += 1,
+            }
+            if let Some(cheats) = result.cheats {
+                *summary.cheats.entry(cheats).or_default() += 1;
+            }
+        }
+        let mut solvable: Vec<&DealResult> = results.iter()
+            .filter(|result| matches!(result.solvability, Solvability::Solvable { .. }))
+            .collect();
+        solvable.sort_by_key(|result| std::cmp::Reverse(result.expanded));
+        summary.hardest = solvable.iter().take(Self::HARDEST).map(|result| result.deal).collect();
+        summary
+    }
+    /// The fraction of deals that are known to be solvable
+    pub fn solvable_fraction(&self) -> f64 {
+        if self.deals == 0 { return 0.0; }
+        self.solvable as f64 / self.deals as f64
+    }
+    /// Writes the distributions as CSV, with a header: each line is the name of the statistic
+    /// (`moves` or `cheats`), a value and the number of solvable deals with that value
+    pub fn write_csv(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        writeln!(out, "statistic,value,deals")?;
+        for (name, distribution) in [("moves", &self.moves), ("cheats", &self.cheats)] {
+            for (value, deals) in distribution {
+                writeln!(out, "{},{},{}", name, value, deals)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Deals:      {}", self.deals)?;
+        writeln!(f, "Solvable:   {} ({:.1}%)", self.solvable, self.solvable_fraction() * 100.0)?;
+        writeln!(f, "Unsolvable: {}", self.unsolvable)?;
+        writeln!(f, "Unknown:    {}", self.unknown)?;
+        if self.invalid > 0 {
+            writeln!(f, "Invalid:    {}", self.invalid)?;
+        }
+        write!(f, "Hardest:    {:?}", self.hardest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survey_matches_solver() {
+        let deals = [3, 1, 2, 4];
+        let options = SolveOptions { max_nodes: Some(5_000), ..SolveOptions::default() };
+        let results = survey(&deals, &options);
+        assert_eq!(results.iter().map(|result| result.deal).collect::<Vec<u64>>(), deals);
+        for result in &results {
+            let outcome = Board::deal(result.deal).solve_optimal_with(LowerBound, MoveCount, &options);
+            assert_eq!(result.expanded, outcome.stats().expanded);
+            match outcome {
+                SolveOutcome::Solved { moves, .. } => {
+                    assert_eq!(result.solvability, Solvability::Solvable { remaining: moves.len() });
+                },
+                SolveOutcome::GaveUp { .. } => assert_eq!(result.solvability, Solvability::Unknown(Limit::Nodes)),
+                outcome => panic!("{:?}", outcome),
+            }
+        }
+        let summary = Summary::new(&results);
+        assert_eq!(summary.solvable + summary.unsolvable + summary.unknown + summary.invalid, deals.len());
+        assert_eq!(summary.invalid, 0);
+        assert_eq!(summary.moves.values().sum::<usize>(), summary.solvable);
+        let mut csv = vec![];
+        write_csv(&results, &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), deals.len() + 1);
+    }
+}